@@ -0,0 +1,310 @@
+//! ADPCM codecs
+//! (ref) IMA Digital Audio Focus and Technical Working Groups, "Recommended Practices for Enhancing Digital Audio Compatibility in Multimedia Systems"
+
+use crate::header::{AdpcmFormat, WavHeader};
+
+/// IMA ADPCM step index adjustment
+const IMA_INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+/// IMA ADPCM quantizer step sizes
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45,
+    50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230,
+    253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+    1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327,
+    3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442,
+    11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// MS ADPCM step adaptation
+const MS_ADAPTATION_TABLE: [i32; 16] = [230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230];
+
+/// MS ADPCM standard coefficient table (used when the fmt chunk has none)
+pub const MS_ADPCM_COEFS: [(i16, i16); 7] = [(256, 0), (512, -256), (0, 0), (192, 64), (240, 0), (460, -208), (392, -232)];
+
+/// default IMA ADPCM block size for the sample rate
+pub fn ima_block_align(sample_rate: u32, channels: u16) -> u16 {
+    let size = if sample_rate <= 11_025 { 256 } else if sample_rate <= 22_050 { 512 } else { 1024 };
+    size * channels.max(1)
+}
+
+/// number of sample frames in an IMA ADPCM block
+/// Only whole groups of 4 bytes per channel hold samples, the rest of the block is padding
+pub fn ima_samples_per_block(block_align: u16, channels: u16) -> u16 {
+    let header_size = 4 * channels.max(1) as u32;
+    if (block_align as u32) < header_size {
+        return 0;
+    }
+    ((block_align as u32 - header_size) / header_size * 8 + 1) as u16
+}
+
+/// number of sample frames in an MS ADPCM block
+pub fn ms_samples_per_block(block_align: u16, channels: u16) -> u16 {
+    let header_size = 7 * channels.max(1) as u32;
+    if (block_align as u32) < header_size {
+        return 0;
+    }
+    ((block_align as u32 - header_size) * 2 / channels.max(1) as u32 + 2) as u16
+}
+
+/// IMA ADPCM block layout of the header, or the default layout
+/// The samples per block always follow from the block align (as written by `ImaEncoder`)
+pub fn ima_format(head: &WavHeader) -> AdpcmFormat {
+    let block_align = match &head.adpcm {
+        Some(f) => f.block_align,
+        None => ima_block_align(head.sample_rate, head.channels),
+    };
+    AdpcmFormat {
+        block_align,
+        samples_per_block: ima_samples_per_block(block_align, head.channels),
+        coefs: vec![],
+    }
+}
+
+/// IMA ADPCM channel state
+#[derive(Debug,Copy,Clone,Default)]
+struct ImaState {
+    predictor: i32,
+    index: i32,
+}
+
+impl ImaState {
+    /// decode a nibble and update the state
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+        if nibble & 1 != 0 { diff += step >> 2; }
+        if nibble & 2 != 0 { diff += step >> 1; }
+        if nibble & 4 != 0 { diff += step; }
+        if nibble & 8 != 0 { diff = -diff; }
+        self.predictor = (self.predictor + diff).clamp(-32768, 32767);
+        self.index = (self.index + IMA_INDEX_TABLE[nibble as usize]).clamp(0, 88);
+        self.predictor as i16
+    }
+    /// encode a sample to a nibble and update the state
+    fn encode(&mut self, sample: i16) -> u8 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut diff = sample as i32 - self.predictor;
+        let mut nibble = 0u8;
+        if diff < 0 {
+            nibble = 8;
+            diff = -diff;
+        }
+        let mut mask = 4;
+        let mut temp_step = step;
+        while mask > 0 {
+            if diff >= temp_step {
+                nibble |= mask;
+                diff -= temp_step;
+            }
+            temp_step >>= 1;
+            mask >>= 1;
+        }
+        // keep the predictor in sync with the decoder
+        self.decode(nibble);
+        nibble
+    }
+}
+
+/// decode an IMA ADPCM block into interleaved f32 samples (at most `samples_per_block` frames)
+/// A short (last) block yields the samples it holds
+pub fn decode_ima_block(block: &[u8], channels: u16, samples_per_block: u16, out: &mut Vec<f32>) {
+    let channels = channels.max(1) as usize;
+    let header_size = 4 * channels;
+    if block.len() < header_size {
+        return;
+    }
+    let mut states = vec![ImaState::default(); channels];
+    for (c, st) in states.iter_mut().enumerate() {
+        let b = &block[c * 4..c * 4 + 4];
+        st.predictor = i16::from_le_bytes([b[0], b[1]]) as i32;
+        st.index = (b[2] as i32).clamp(0, 88);
+    }
+    // 8 samples per channel in each group of 4 bytes
+    let groups = (block.len() - header_size) / header_size;
+    let mut samples = vec![0i16; (1 + groups * 8) * channels];
+    for (c, st) in states.iter().enumerate() {
+        samples[c] = st.predictor as i16;
+    }
+    for g in 0..groups {
+        for (c, st) in states.iter_mut().enumerate() {
+            let base = header_size + (g * channels + c) * 4;
+            for k in 0..8 {
+                let byte = block[base + k / 2];
+                let nibble = if k % 2 == 0 { byte & 0x0F } else { byte >> 4 };
+                samples[(1 + g * 8 + k) * channels + c] = st.decode(nibble);
+            }
+        }
+    }
+    samples.truncate(samples_per_block as usize * channels);
+    out.extend(samples.iter().map(|v| *v as f32 / 32768.0));
+}
+
+/// MS ADPCM channel state
+#[derive(Debug,Copy,Clone,Default)]
+struct MsState {
+    coef: (i32, i32),
+    delta: i32,
+    sample1: i32,
+    sample2: i32,
+}
+
+impl MsState {
+    /// decode a nibble and update the state
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let signed = if nibble >= 8 { nibble as i32 - 16 } else { nibble as i32 };
+        let predictor = (self.sample1 * self.coef.0 + self.sample2 * self.coef.1) >> 8;
+        let sample = (predictor + signed * self.delta).clamp(-32768, 32767);
+        self.sample2 = self.sample1;
+        self.sample1 = sample;
+        self.delta = ((MS_ADAPTATION_TABLE[nibble as usize] * self.delta) >> 8).max(16);
+        sample as i16
+    }
+}
+
+/// decode an MS ADPCM block into interleaved f32 samples (at most `samples_per_block` frames)
+/// A short (last) block yields the samples it holds
+pub fn decode_ms_block(block: &[u8], channels: u16, samples_per_block: u16, coefs: &[(i16, i16)], out: &mut Vec<f32>) {
+    let channels = channels.max(1) as usize;
+    let header_size = 7 * channels;
+    if block.len() < header_size || coefs.is_empty() {
+        return;
+    }
+    // header: predictor index, delta, sample1, sample2 (each for all channels)
+    let read_i16 = |pos: usize| i16::from_le_bytes([block[pos], block[pos + 1]]) as i32;
+    let mut states = vec![MsState::default(); channels];
+    for (c, st) in states.iter_mut().enumerate() {
+        let (c1, c2) = coefs[(block[c] as usize).min(coefs.len() - 1)];
+        st.coef = (c1 as i32, c2 as i32);
+        st.delta = read_i16(channels + c * 2);
+        st.sample1 = read_i16(channels * 3 + c * 2);
+        st.sample2 = read_i16(channels * 5 + c * 2);
+    }
+    let nibbles = (block.len() - header_size) * 2;
+    let mut samples = Vec::with_capacity(nibbles + 2 * channels);
+    // sample2 is the first sample of the block
+    samples.extend(states.iter().map(|st| st.sample2 as i16));
+    samples.extend(states.iter().map(|st| st.sample1 as i16));
+    // high nibble first, channels interleaved
+    for i in 0..(nibbles / channels * channels) {
+        let byte = block[header_size + i / 2];
+        let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0x0F };
+        samples.push(states[i % channels].decode(nibble));
+    }
+    samples.truncate(samples_per_block as usize * channels);
+    out.extend(samples.iter().map(|v| *v as f32 / 32768.0));
+}
+
+/// IMA ADPCM encoder
+/// The step index is carried over from block to block
+pub struct ImaEncoder {
+    channels: usize,
+    block_align: usize,
+    states: Vec<ImaState>,
+}
+
+impl ImaEncoder {
+    pub fn new(channels: u16, block_align: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            channels,
+            block_align: block_align as usize,
+            states: vec![ImaState::default(); channels],
+        }
+    }
+    /// number of interleaved samples in a block
+    pub fn block_samples(&self) -> usize {
+        ima_samples_per_block(self.block_align as u16, self.channels as u16) as usize * self.channels
+    }
+    /// encode interleaved samples to blocks, the last block is padded with silence
+    pub fn encode(&mut self, samples: &[f32]) -> Vec<u8> {
+        let block_samples = self.block_samples();
+        let mut result = Vec::new();
+        if block_samples == 0 {
+            return result;
+        }
+        for chunk in samples.chunks(block_samples) {
+            let mut block_in: Vec<i16> = chunk.iter()
+                .map(|v| (*v * 32768f32).clamp(-32768.0, 32767.0) as i16)
+                .collect();
+            block_in.resize(block_samples, 0);
+            self.encode_block(&block_in, &mut result);
+        }
+        result
+    }
+    fn encode_block(&mut self, samples: &[i16], out: &mut Vec<u8>) {
+        let channels = self.channels;
+        let start = out.len();
+        // header: first sample, step index
+        for (c, st) in self.states.iter_mut().enumerate() {
+            st.predictor = samples[c] as i32;
+            out.extend_from_slice(&samples[c].to_le_bytes());
+            out.push(st.index as u8);
+            out.push(0);
+        }
+        let groups = (self.block_align - 4 * channels) / (4 * channels);
+        for g in 0..groups {
+            for (c, st) in self.states.iter_mut().enumerate() {
+                for k in (0..8).step_by(2) {
+                    let lo = st.encode(samples[(1 + g * 8 + k) * channels + c]);
+                    let hi = st.encode(samples[(2 + g * 8 + k) * channels + c]);
+                    out.push(lo | (hi << 4));
+                }
+            }
+        }
+        out.resize(start + self.block_align, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn ima_layout() {
+        assert_eq!(ima_block_align(8_000, 1), 256);
+        assert_eq!(ima_block_align(44_100, 2), 2048);
+        assert_eq!(ima_samples_per_block(256, 1), 505);
+        assert_eq!(ima_samples_per_block(2048, 2), 2041);
+        // the 2 bytes after the last whole group are padding
+        assert_eq!(ima_samples_per_block(38, 1), 65);
+        assert_eq!(ima_samples_per_block(3, 1), 0);
+    }
+    #[test]
+    fn ms_decode_block() {
+        assert_eq!(ms_samples_per_block(256, 1), 500);
+        assert_eq!(ms_samples_per_block(512, 2), 500);
+        // mono block: predictor 0 (256, 0), delta 16, sample1 100, sample2 50
+        let mut block = vec![0u8];
+        block.extend_from_slice(&16i16.to_le_bytes());
+        block.extend_from_slice(&100i16.to_le_bytes());
+        block.extend_from_slice(&50i16.to_le_bytes());
+        block.push(0x1F); // +1, -1
+        let mut out = vec![];
+        decode_ms_block(&block, 1, ms_samples_per_block(8, 1), &MS_ADPCM_COEFS, &mut out);
+        let v: Vec<i16> = out.iter().map(|v| (v * 32768.0) as i16).collect();
+        // 50, 100, then 100 + 1 * 16, then 116 - 1 * 16 (delta stays at the minimum 16)
+        assert_eq!(v, vec![50, 100, 116, 100]);
+        out.clear();
+        decode_ms_block(&block, 1, 3, &MS_ADPCM_COEFS, &mut out);
+        assert_eq!(out.len(), 3);
+    }
+    #[test]
+    fn ima_roundtrip() {
+        let channels = 2;
+        let samples: Vec<f32> = (0..3_000)
+            .map(|i| ((i / 2) as f32 / 8_000.0 * 440.0 * 2.0 * std::f32::consts::PI).sin() * 0.5)
+            .collect();
+        let mut enc = ImaEncoder::new(channels, 512);
+        let bytes = enc.encode(&samples);
+        assert_eq!(bytes.len() % 512, 0);
+        let mut decoded = vec![];
+        for block in bytes.chunks(512) {
+            decode_ima_block(block, channels, ima_samples_per_block(512, channels), &mut decoded);
+        }
+        assert!(decoded.len() >= samples.len());
+        // the step size adapts within the first samples
+        for (a, b) in decoded.iter().zip(samples.iter()).skip(32) {
+            assert!((a - b).abs() < 0.05, "{a} != {b}");
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! AIFF / AIFF-C reader and writer
+//! (ref) Audio Interchange File Format 1.3, AIFF-C (Apple 1991)
+//!
+//! AIFF files are read by `reader::Reader` like WAV files (the `FORM` tag is detected by `read_header`).
+//! Samples are big-endian, `sowt` AIFF-C files hold little-endian samples.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use crate::header::*;
+use crate::reader::{Reader, DecodeError};
+use crate::writer::{Writer, EncoderError};
+
+/// AIFF-C version 1 (FVER chunk timestamp)
+const AIFC_VERSION1: u32 = 0xA2805140;
+
+/// AIFF text chunks and the LIST INFO ids they are mapped to
+const TEXT_CHUNKS: [(&str, &str); 4] = [
+    ("NAME", "INAM"),
+    ("AUTH", "IART"),
+    ("(c) ", "ICOP"),
+    ("ANNO", "ICMT"),
+];
+
+/// WavData to AIFF file
+pub fn to_file(file_out: &mut File, wav: &WavData) -> Result<(), EncoderError> {
+    let bytes = to_bytes(&wav.header, &wav.samples)?;
+    file_out.write_all(&bytes).map_err(|err| EncoderError::IOError { source: err })
+}
+
+/// WavData to AIFF bytes
+/// Integer samples are written as AIFF, float and G.711 samples as AIFF-C
+pub fn to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, EncoderError> {
+    let mut w = Writer::new();
+    w.set_container(ContainerKind::Aiff);
+    w.from_scratch(head, samples)?;
+    Ok(w.to_bytes())
+}
+
+/// Read the AIFF header after the `FORM` tag
+/// The cursor is left at the start of the samples in the SSND chunk
+pub fn read_header<R: Read + Seek>(r: &mut Reader<R>) -> Result<WavHeader, DecodeError> {
+    r.endian = Endian::Big;
+    r.layout = SampleLayout { endian: Endian::Big, signed_8bit: true };
+    let form_size = r.read_u32().unwrap_or(0);
+    if form_size < 4 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "chunk size",
+            expected: 3,
+            found: form_size,
+        });
+    }
+    let form_type = r.read_str4();
+    r.container = match form_type.as_str() {
+        "AIFF" => ContainerKind::Aiff,
+        "AIFC" => ContainerKind::Aifc,
+        _ => return Err(DecodeError::InvalidTag { expected: "AIFF", found: form_type }),
+    };
+
+    // SSND may come before COMM, so all chunks are read first
+    let mut header = WavHeader::new();
+    let mut frames = None;
+    let mut ssnd = None;
+    let mut items = vec![];
+    loop {
+        let chunk_tag = r.read_str4();
+        if chunk_tag.is_empty() {
+            break;
+        }
+        let size = r.read_u32().unwrap_or(0) as u64;
+        match chunk_tag.as_str() {
+            "COMM" => frames = Some(read_comm_chunk(r, size, &mut header)?),
+            "SSND" if size >= 8 => {
+                let offset = r.read_u32().unwrap_or(0) as u64;
+                let _block_size = r.read_u32();
+                let pos = r.position()? + offset;
+                ssnd = Some((pos, (size - 8).saturating_sub(offset)));
+                r.skip(size - 8 + size % 2)?;
+            },
+            "NAME" | "AUTH" | "(c) " | "ANNO" => {
+                let data = r.read_chunk_data(size)?;
+                let id = TEXT_CHUNKS.iter().find(|(tag, _)| *tag == chunk_tag).map_or("ICMT", |(_, id)| id);
+                items.push(ListChunkItem {
+                    id: id.to_string(),
+                    value: String::from_utf8_lossy(&data).trim_end_matches('\0').to_string(),
+                });
+            },
+            _ => r.skip(size + size % 2)?,
+        }
+    }
+    let Some(frames) = frames else {
+        return Err(DecodeError::InvalidTag { expected: "COMM", found: String::new() });
+    };
+    let Some((pos, data_size)) = ssnd else {
+        return Err(DecodeError::InvalidTag { expected: "SSND", found: String::new() });
+    };
+    if !items.is_empty() {
+        header.list_chunk = Some(ListChunk { items });
+    }
+    // COMM has the exact number of sample frames
+    let frame_size = (header.bits_per_sample / 8) as u64 * header.channels as u64;
+    r.seek_to(pos)?;
+    r.start_data(&header, data_size.min(frames * frame_size));
+    Ok(header)
+}
+
+/// Read the body of a COMM chunk, returns the number of sample frames
+fn read_comm_chunk<R: Read + Seek>(r: &mut Reader<R>, chunk_size: u64, header: &mut WavHeader) -> Result<u64, DecodeError> {
+    if chunk_size < 18 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "COMM chunk size",
+            expected: 17,
+            found: chunk_size as u32,
+        });
+    }
+    let channels = r.read_u16().unwrap_or(0);
+    if channels == 0 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "channels",
+            expected: 0,
+            found: 0,
+        });
+    }
+    let frames = r.read_u32().unwrap_or(0) as u64;
+    let bits_per_sample = r.read_u16().unwrap_or(0);
+    let sample_rate = extended_to_f64(r.read_bytes::<10>().unwrap_or_default()).round();
+    if sample_rate < 1.0 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "sample rate",
+            expected: 0,
+            found: 0,
+        });
+    }
+    let mut read_size = 18;
+    // AIFF-C compression type (the compression name is skipped)
+    let mut compression = String::from("NONE");
+    if r.container == ContainerKind::Aifc && chunk_size >= 22 {
+        compression = r.read_str4();
+        read_size = 22;
+    }
+    header.channels = channels;
+    header.sample_rate = sample_rate.min(u32::MAX as f64) as u32;
+    // samples are stored in whole bytes
+    header.bits_per_sample = bits_per_sample.div_ceil(8) * 8;
+    match compression.as_str() {
+        "NONE" | "twos" => header.sample_format = SampleFormat::Int,
+        "sowt" => {
+            header.sample_format = SampleFormat::Int;
+            r.layout.endian = Endian::Little;
+        },
+        "fl32" | "FL32" => {
+            header.sample_format = SampleFormat::Float;
+            header.bits_per_sample = 32;
+        },
+        "fl64" | "FL64" => {
+            header.sample_format = SampleFormat::Float;
+            header.bits_per_sample = 64;
+        },
+        "ulaw" | "ULAW" => {
+            header.sample_format = SampleFormat::WaveFormatMuLaw;
+            header.bits_per_sample = 8;
+        },
+        "alaw" | "ALAW" => {
+            header.sample_format = SampleFormat::WaveFromatALaw;
+            header.bits_per_sample = 8;
+        },
+        _ => return Err(DecodeError::UnsupportedEncoding),
+    }
+    r.skip(chunk_size - read_size + chunk_size % 2)?;
+    Ok(frames)
+}
+
+/// Write the AIFF header (FORM, FVER, COMM and text chunks) for `data_size` bytes of samples
+/// Called by `Writer::write_riff_header` for the AIFF containers
+pub(crate) fn write_header(w: &mut Writer, head: &WavHeader, data_size: u64) -> Result<(), EncoderError> {
+    let (compression, compression_name): (&str, &str) = match (head.sample_format, head.bits_per_sample) {
+        (SampleFormat::Int, 8 | 16 | 24 | 32) => ("NONE", "not compressed"),
+        (SampleFormat::Int, _) => return Err(EncoderError::UnsupportedFormat {
+            attribute: "bits per integer sample",
+            expected: &[8, 16, 24, 32],
+            found: head.bits_per_sample as u32,
+        }),
+        (SampleFormat::Float, 32) => ("fl32", "32-bit floating point"),
+        (SampleFormat::Float, 64) => ("fl64", "64-bit floating point"),
+        (SampleFormat::Float, _) => return Err(EncoderError::UnsupportedFormat {
+            attribute: "bits per float sample",
+            expected: &[32, 64],
+            found: head.bits_per_sample as u32,
+        }),
+        (SampleFormat::WaveFormatMuLaw, 8) => ("ulaw", "\u{b5}Law 2:1"),
+        (SampleFormat::WaveFromatALaw, 8) => ("alaw", "ALaw 2:1"),
+        _ => return Err(EncoderError::UnsupportedEncoding),
+    };
+    // plain AIFF only holds integer samples
+    if compression != "NONE" {
+        w.container = ContainerKind::Aifc;
+    }
+    let aifc = w.container == ContainerKind::Aifc;
+    // G.711 samples are 16 bits after expansion
+    let bits_per_sample = if compression.ends_with("law") { 16 } else { head.bits_per_sample };
+    let frame_size = (head.bits_per_sample / 8) as u64 * head.channels.max(1) as u64;
+    let frames = data_size / frame_size.max(1);
+    // pascal string padded to an even length
+    let mut name = vec![compression_name.len() as u8];
+    name.extend_from_slice(compression_name.as_bytes());
+    if name.len() % 2 == 1 {
+        name.push(0);
+    }
+    let comm_size = if aifc { 22 + name.len() as u64 } else { 18 };
+    // text chunks from the LIST INFO items
+    let mut texts = vec![];
+    if let Some(list) = &head.list_chunk {
+        for it in list.items.iter() {
+            if let Some((tag, _)) = TEXT_CHUNKS.iter().find(|(_, id)| *id == it.id) {
+                texts.push((*tag, it.value.as_bytes()));
+            }
+        }
+    }
+    let texts_total: u64 = texts.iter().map(|(_, v)| 8 + v.len() as u64 + v.len() as u64 % 2).sum();
+    // FORM type(4) + FVER(12) + COMM + texts + SSND(8 + offset and block size(8) + data + pad)
+    let fver_total = if aifc { 12 } else { 0 };
+    let form_size = 4 + fver_total + (8 + comm_size) + texts_total + (16 + data_size + data_size % 2);
+    w.write_str("FORM");
+    w.write_u32(form_size.min(u32::MAX as u64) as u32);
+    w.write_str(if aifc { "AIFC" } else { "AIFF" });
+    if aifc {
+        w.write_str("FVER");
+        w.write_u32(4);
+        w.write_u32(AIFC_VERSION1);
+    }
+    w.write_str("COMM");
+    w.write_u32(comm_size as u32);
+    w.write_u16(head.channels);
+    w.fact_pos = Some(w.position());
+    w.write_u32(frames.min(u32::MAX as u64) as u32);
+    w.write_u16(bits_per_sample);
+    w.write_bytes(&rate_to_extended(head.sample_rate));
+    if aifc {
+        w.write_str(compression);
+        w.write_bytes(&name);
+    }
+    for (tag, value) in texts {
+        w.write_str(tag);
+        w.write_u32(value.len() as u32);
+        w.write_bytes(value);
+        w.write_padding(value.len() as u64);
+    }
+    Ok(())
+}
+
+/// 80-bit IEEE 754 extended precision float (big-endian) to f64
+pub fn extended_to_f64(b: [u8; 10]) -> f64 {
+    let exponent = (((b[0] & 0x7F) as i32) << 8) | b[1] as i32;
+    let mantissa = u64::from_be_bytes([b[2], b[3], b[4], b[5], b[6], b[7], b[8], b[9]]);
+    if exponent == 0 && mantissa == 0 {
+        return 0.0;
+    }
+    let v = mantissa as f64 * 2f64.powi(exponent - 16383 - 63);
+    if b[0] & 0x80 != 0 { -v } else { v }
+}
+
+/// sample rate to 80-bit IEEE 754 extended precision float (big-endian)
+pub fn rate_to_extended(rate: u32) -> [u8; 10] {
+    let mut b = [0u8; 10];
+    if rate == 0 {
+        return b;
+    }
+    // normalized mantissa with the explicit integer bit
+    let shift = rate.leading_zeros() + 32;
+    let exponent = 16383 + 63 - shift as u16;
+    b[0..2].copy_from_slice(&exponent.to_be_bytes());
+    b[2..10].copy_from_slice(&((rate as u64) << shift).to_be_bytes());
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn extended_sample_rate() {
+        // 44100 Hz as written by most tools
+        let b = [0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0];
+        assert_eq!(extended_to_f64(b), 44_100.0);
+        assert_eq!(rate_to_extended(44_100), b);
+        for rate in [1, 8_000, 22_050, 48_000, 96_000, 192_000] {
+            assert_eq!(extended_to_f64(rate_to_extended(rate)), rate as f64);
+        }
+    }
+
+    #[test]
+    fn aiff_roundtrip() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.list_chunk = Some(ListChunk {
+            items: vec![ListChunkItem { id: "INAM".to_string(), value: "title".to_string() }],
+        });
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        let bytes = to_bytes(&head, &samples).unwrap();
+        // FORM(12) + COMM(8 + 18) + NAME(8 + 6) + SSND(16) + 8
+        assert_eq!(bytes.len(), 12 + 26 + 14 + 16 + 8);
+        assert_eq!(&bytes[0..4], b"FORM");
+        assert_eq!(u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"AIFF");
+        assert_eq!(u32::from_be_bytes(bytes[22..26].try_into().unwrap()), 2); // numSampleFrames
+        // big-endian samples
+        assert_eq!(&bytes[68..70], &[0x40, 0x00]);
+
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Aiff);
+        assert_eq!(h.channels, 2);
+        assert_eq!(h.sample_rate, 44_100);
+        assert_eq!(h.bits_per_sample, 16);
+        assert_eq!(h.list_chunk, head.list_chunk);
+        for (a, b) in r.get_samples_f32().unwrap().iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+
+        // 8-bit samples are signed
+        head.bits_per_sample = 8;
+        let bytes = to_bytes(&head, &samples).unwrap();
+        assert_eq!(bytes[bytes.len() - 4] as i8, 63);
+        let read = Reader::from_vec(bytes.clone()).unwrap().get_samples_f32().unwrap();
+        for (a, b) in read.iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 0.01, "{a} != {b}");
+        }
+
+        // streaming writer patches FORM, SSND and COMM
+        let mut cur = std::io::Cursor::new(Vec::new());
+        let mut w = crate::writer::WavWriter::with_container(&mut cur, &head, ContainerKind::Aiff).unwrap();
+        w.write_samples(&samples[..1]).unwrap();
+        w.write_samples(&samples[1..]).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), bytes);
+    }
+
+    #[test]
+    fn aifc_float_and_sowt() {
+        let head = WavHeader::new_mono();
+        let samples = vec![0.5, -0.5, 0.125];
+        let bytes = to_bytes(&head, &samples).unwrap();
+        assert_eq!(&bytes[8..12], b"AIFC");
+        assert_eq!(&bytes[12..16], b"FVER");
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Aifc);
+        assert_eq!(h.sample_format, SampleFormat::Float);
+        assert_eq!(r.get_samples_f32().unwrap(), samples);
+
+        // sowt: little-endian 16-bit samples, SSND before COMM
+        let mut data = vec![];
+        data.extend_from_slice(b"FORM\0\0\0\0AIFC");
+        data.extend_from_slice(b"SSND");
+        data.extend_from_slice(&12u32.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        for v in [0x4000i16, -0x4000] { data.extend_from_slice(&v.to_le_bytes()); }
+        data.extend_from_slice(b"COMM");
+        data.extend_from_slice(&24u32.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&16u16.to_be_bytes());
+        data.extend_from_slice(&rate_to_extended(8_000));
+        data.extend_from_slice(b"sowt\0\0");
+        let len = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&len.to_be_bytes());
+        let mut r = Reader::from_vec(data).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_rate, 8_000);
+        let read = r.get_samples_f32().unwrap();
+        assert_eq!(read.len(), 2);
+        assert!((read[0] - 0.5).abs() < 1e-4 && (read[1] + 0.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,204 @@
+//! Sun/NeXT audio (.au / .snd) reader and writer
+//! (ref) https://en.wikipedia.org/wiki/Au_file_format
+//!
+//! .au files are read by `reader::Reader` like WAV files (the `.snd` magic is detected by `read_header`).
+//! The annotation field is mapped to the ICMT item of the LIST INFO chunk.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use crate::header::*;
+use crate::reader::{Reader, DecodeError};
+use crate::writer::{Writer, EncoderError};
+
+/// data size field value for an unknown size (read up to the end of the file)
+const UNKNOWN_SIZE: u32 = 0xFFFFFFFF;
+/// size of the fixed header fields
+const HEADER_SIZE: u32 = 24;
+
+/// .au encodings and the sample format / bits they are mapped to
+const ENCODINGS: [(u32, SampleFormat, u16); 8] = [
+    (1, SampleFormat::WaveFormatMuLaw, 8),
+    (2, SampleFormat::Int, 8),
+    (3, SampleFormat::Int, 16),
+    (4, SampleFormat::Int, 24),
+    (5, SampleFormat::Int, 32),
+    (6, SampleFormat::Float, 32),
+    (7, SampleFormat::Float, 64),
+    (27, SampleFormat::WaveFromatALaw, 8),
+];
+
+/// WavData to .au file
+pub fn to_file(file_out: &mut File, wav: &WavData) -> Result<(), EncoderError> {
+    let bytes = to_bytes(&wav.header, &wav.samples)?;
+    file_out.write_all(&bytes).map_err(|err| EncoderError::IOError { source: err })
+}
+
+/// WavData to .au bytes
+pub fn to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, EncoderError> {
+    let mut w = Writer::new();
+    w.set_container(ContainerKind::Au);
+    w.from_scratch(head, samples)?;
+    Ok(w.to_bytes())
+}
+
+/// Read the .au header after the `.snd` magic
+/// The cursor is left at the start of the samples
+pub fn read_header<R: Read + Seek>(r: &mut Reader<R>) -> Result<WavHeader, DecodeError> {
+    r.container = ContainerKind::Au;
+    r.endian = Endian::Big;
+    r.layout = SampleLayout { endian: Endian::Big, signed_8bit: true };
+    let data_offset = r.read_u32().unwrap_or(0);
+    let data_size = r.read_u32().unwrap_or(0);
+    let encoding = r.read_u32().unwrap_or(0);
+    let sample_rate = r.read_u32().unwrap_or(0);
+    let channels = r.read_u32().unwrap_or(0);
+    if data_offset < HEADER_SIZE {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "data offset",
+            expected: HEADER_SIZE,
+            found: data_offset,
+        });
+    }
+    if channels == 0 || channels > u16::MAX as u32 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "channels",
+            expected: 0,
+            found: channels,
+        });
+    }
+    if sample_rate == 0 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "sample rate",
+            expected: 0,
+            found: 0,
+        });
+    }
+    let Some((_, sample_format, bits)) = ENCODINGS.iter().find(|(e, _, _)| *e == encoding) else {
+        return Err(DecodeError::UnsupportedEncoding);
+    };
+    let mut header = WavHeader::new();
+    header.sample_format = *sample_format;
+    header.bits_per_sample = *bits;
+    header.sample_rate = sample_rate;
+    header.channels = channels as u16;
+    // annotation (usually a null terminated string)
+    let annotation = r.read_chunk_data((data_offset - HEADER_SIZE) as u64)?;
+    let text = annotation.split(|b| *b == 0).next().unwrap_or(&[]);
+    if !text.is_empty() {
+        header.list_chunk = Some(ListChunk {
+            items: vec![ListChunkItem { id: "ICMT".to_string(), value: String::from_utf8_lossy(text).to_string() }],
+        });
+    }
+    let data_size = if data_size == UNKNOWN_SIZE { u64::MAX } else { data_size as u64 };
+    r.start_data(&header, data_size);
+    Ok(header)
+}
+
+/// Write the .au header and annotation
+/// Called by `Writer::write_riff_header` for the .au container
+pub(crate) fn write_header(w: &mut Writer, head: &WavHeader, data_size: u64) -> Result<(), EncoderError> {
+    let Some((encoding, _, _)) = ENCODINGS.iter()
+        .find(|(_, f, bits)| *f == head.sample_format && *bits == head.bits_per_sample) else {
+        return match head.sample_format {
+            SampleFormat::Int => Err(EncoderError::UnsupportedFormat {
+                attribute: "bits per integer sample",
+                expected: &[8, 16, 24, 32],
+                found: head.bits_per_sample as u32,
+            }),
+            SampleFormat::Float => Err(EncoderError::UnsupportedFormat {
+                attribute: "bits per float sample",
+                expected: &[32, 64],
+                found: head.bits_per_sample as u32,
+            }),
+            _ => Err(EncoderError::UnsupportedEncoding),
+        };
+    };
+    // the annotation is null terminated and padded to a multiple of 4 bytes (at least 4 bytes)
+    let mut annotation = head.list_chunk.as_ref()
+        .and_then(|list| list.items.iter().find(|it| it.id == "ICMT"))
+        .map(|it| it.value.as_bytes().to_vec())
+        .unwrap_or_default();
+    annotation.push(0);
+    annotation.resize(annotation.len().div_ceil(4) * 4, 0);
+    w.write_str(".snd");
+    w.write_u32(HEADER_SIZE + annotation.len() as u32);
+    w.data_size_pos = Some(w.position());
+    w.write_u32(data_size.min(UNKNOWN_SIZE as u64) as u32);
+    w.write_u32(*encoding);
+    w.write_u32(head.sample_rate);
+    w.write_u32(head.channels as u32);
+    w.write_bytes(&annotation);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn au_roundtrip() {
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.sample_rate = 8_000;
+        head.list_chunk = Some(ListChunk {
+            items: vec![ListChunkItem { id: "ICMT".to_string(), value: "speaker 12".to_string() }],
+        });
+        let samples = vec![0.5, -0.5, 0.25];
+        let bytes = to_bytes(&head, &samples).unwrap();
+        // header(24) + annotation(12) + samples(6)
+        assert_eq!(bytes.len(), 24 + 12 + 6);
+        assert_eq!(&bytes[0..4], b".snd");
+        assert_eq!(u32::from_be_bytes(bytes[4..8].try_into().unwrap()), 36);
+        assert_eq!(u32::from_be_bytes(bytes[8..12].try_into().unwrap()), 6);
+        assert_eq!(u32::from_be_bytes(bytes[12..16].try_into().unwrap()), 3);
+        assert_eq!(u32::from_be_bytes(bytes[16..20].try_into().unwrap()), 8_000);
+        assert_eq!(&bytes[24..36], b"speaker 12\0\0");
+        // big-endian signed samples
+        assert_eq!(&bytes[36..38], &[0x40, 0x00]);
+
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Au);
+        assert_eq!(h.channels, 1);
+        assert_eq!(h.sample_rate, 8_000);
+        assert_eq!(h.bits_per_sample, 16);
+        assert_eq!(h.list_chunk, head.list_chunk);
+        for (a, b) in r.get_samples_f32().unwrap().iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+
+        // streaming writer patches the data size
+        let mut cur = std::io::Cursor::new(Vec::new());
+        let mut w = crate::writer::WavWriter::with_container(&mut cur, &head, ContainerKind::Au).unwrap();
+        w.write_samples(&samples[..1]).unwrap();
+        w.write_samples(&samples[1..]).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), bytes);
+    }
+
+    #[test]
+    fn au_mulaw_unknown_size() {
+        let mut data = vec![];
+        data.extend_from_slice(b".snd");
+        for v in [28, UNKNOWN_SIZE, 1, 8_000, 1] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        data.extend_from_slice(&[0; 4]);
+        // µ-law silence and full scale
+        data.extend_from_slice(&[0xFF, 0x80, 0x00]);
+        let mut r = Reader::from_vec(data).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::WaveFormatMuLaw);
+        assert_eq!(h.list_chunk, None);
+        let samples = r.get_samples_f32().unwrap();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[1] > 0.9 && samples[2] < -0.9);
+
+        // 12-bit samples have no .au encoding
+        let mut head = WavHeader::new_mono();
+        head.bits_per_sample = 12;
+        head.set_int_format();
+        assert!(to_bytes(&head, &[0.0]).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Core Audio Format (CAF) reader and writer
+//! (ref) Apple Core Audio Format Specification 1.0
+//!
+//! CAF files are read by `reader::Reader` like WAV files (the `caff` tag is detected by `read_header`).
+//! Only constant bit rate formats are supported: linear PCM, µ-law and A-law.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use crate::header::*;
+use crate::reader::{Reader, DecodeError};
+use crate::writer::{Writer, EncoderError};
+
+/// kCAFLinearPCMFormatFlagIsFloat
+const FLAG_IS_FLOAT: u32 = 1;
+/// kCAFLinearPCMFormatFlagIsLittleEndian
+const FLAG_IS_LITTLE_ENDIAN: u32 = 2;
+
+/// CAF info keys and the LIST INFO ids they are mapped to
+const INFO_KEYS: [(&str, &str); 10] = [
+    ("title", "INAM"),
+    ("artist", "IART"),
+    ("album", "IPRD"),
+    ("genre", "IGNR"),
+    ("comments", "ICMT"),
+    ("track number", "ITRK"),
+    ("recorded date", "ICRD"),
+    ("year", "ICRD"),
+    ("copyright", "ICOP"),
+    ("encoding application", "ISFT"),
+];
+
+/// WavData to CAF file
+pub fn to_file(file_out: &mut File, wav: &WavData) -> Result<(), EncoderError> {
+    let bytes = to_bytes(&wav.header, &wav.samples)?;
+    file_out.write_all(&bytes).map_err(|err| EncoderError::IOError { source: err })
+}
+
+/// WavData to CAF bytes
+pub fn to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, EncoderError> {
+    let mut w = Writer::new();
+    w.set_container(ContainerKind::Caf);
+    w.from_scratch(head, samples)?;
+    Ok(w.to_bytes())
+}
+
+/// Read the CAF header after the `caff` tag
+/// The cursor is left at the start of the samples in the data chunk
+pub fn read_header<R: Read + Seek>(r: &mut Reader<R>) -> Result<WavHeader, DecodeError> {
+    r.container = ContainerKind::Caf;
+    r.endian = Endian::Big;
+    r.layout = SampleLayout { endian: Endian::Big, signed_8bit: true };
+    let version = r.read_u16().unwrap_or(0);
+    if version != 1 {
+        return Err(DecodeError::UnsupportedWav {
+            attribute: "CAF file version",
+            expected: &[1],
+            found: version as u32,
+        });
+    }
+    let _flags = r.read_u16();
+
+    let mut header = WavHeader::new();
+    let mut has_desc = false;
+    let mut data = None;
+    let mut items = vec![];
+    loop {
+        let chunk_tag = r.read_str4();
+        if chunk_tag.is_empty() {
+            break;
+        }
+        // -1 (only for the data chunk) means up to the end of the file
+        let size = r.read_u64().unwrap_or(0);
+        match chunk_tag.as_str() {
+            "desc" => {
+                read_desc_chunk(r, size, &mut header)?;
+                has_desc = true;
+            },
+            "info" => read_info_chunk(r, size, &mut items)?,
+            "data" => {
+                let _edit_count = r.read_u32();
+                let pos = r.position()?;
+                if size == u64::MAX {
+                    data = Some((pos, u64::MAX));
+                    break;
+                }
+                data = Some((pos, size.saturating_sub(4)));
+                r.skip(size.saturating_sub(4))?;
+            },
+            _ => r.skip(size)?,
+        }
+    }
+    if !has_desc {
+        return Err(DecodeError::InvalidTag { expected: "desc", found: String::new() });
+    }
+    let Some((pos, data_size)) = data else {
+        return Err(DecodeError::InvalidTag { expected: "data", found: String::new() });
+    };
+    if !items.is_empty() {
+        header.list_chunk = Some(ListChunk { items });
+    }
+    r.seek_to(pos)?;
+    r.start_data(&header, data_size);
+    Ok(header)
+}
+
+/// Read the audio description chunk
+fn read_desc_chunk<R: Read + Seek>(r: &mut Reader<R>, chunk_size: u64, header: &mut WavHeader) -> Result<(), DecodeError> {
+    if chunk_size < 32 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "desc chunk size",
+            expected: 31,
+            found: chunk_size as u32,
+        });
+    }
+    let sample_rate = r.read_f64().unwrap_or(0.0).round();
+    let format_id = r.read_str4();
+    let format_flags = r.read_u32().unwrap_or(0);
+    let bytes_per_packet = r.read_u32().unwrap_or(0);
+    let frames_per_packet = r.read_u32().unwrap_or(0);
+    let channels = r.read_u32().unwrap_or(0);
+    let bits_per_channel = r.read_u32().unwrap_or(0);
+    if channels == 0 || channels > u16::MAX as u32 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "channels",
+            expected: 0,
+            found: channels,
+        });
+    }
+    if sample_rate < 1.0 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "sample rate",
+            expected: 0,
+            found: 0,
+        });
+    }
+    // packets of one frame only (no packet table)
+    if frames_per_packet != 1 {
+        return Err(DecodeError::UnsupportedWav {
+            attribute: "frames per packet",
+            expected: &[1],
+            found: frames_per_packet,
+        });
+    }
+    header.channels = channels as u16;
+    header.sample_rate = sample_rate.min(u32::MAX as f64) as u32;
+    // the sample width in bytes comes from the packet size
+    header.bits_per_sample = match bytes_per_packet / channels {
+        0 => bits_per_channel.div_ceil(8) as u16 * 8,
+        n => (n * 8) as u16,
+    };
+    match format_id.as_str() {
+        "lpcm" => {
+            header.sample_format = if format_flags & FLAG_IS_FLOAT != 0 { SampleFormat::Float } else { SampleFormat::Int };
+            if format_flags & FLAG_IS_LITTLE_ENDIAN != 0 {
+                r.layout.endian = Endian::Little;
+            }
+        },
+        "ulaw" => header.sample_format = SampleFormat::WaveFormatMuLaw,
+        "alaw" => header.sample_format = SampleFormat::WaveFromatALaw,
+        _ => return Err(DecodeError::UnsupportedEncoding),
+    }
+    r.skip(chunk_size - 32)
+}
+
+/// Read the info chunk (pairs of null terminated key and value strings)
+fn read_info_chunk<R: Read + Seek>(r: &mut Reader<R>, chunk_size: u64, items: &mut Vec<ListChunkItem>) -> Result<(), DecodeError> {
+    let data = r.read_chunk_data(chunk_size)?;
+    if data.len() < 4 {
+        return Ok(());
+    }
+    let strings: Vec<String> = data[4..]
+        .split(|b| *b == 0)
+        .map(|s| String::from_utf8_lossy(s).to_string())
+        .collect();
+    for pair in strings.chunks_exact(2) {
+        let key = pair[0].to_lowercase();
+        if let Some((_, id)) = INFO_KEYS.iter().find(|(k, _)| *k == key) {
+            items.push(ListChunkItem { id: id.to_string(), value: pair[1].clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Write the CAF file header, desc and info chunks
+/// Called by `Writer::write_riff_header` for the CAF container
+pub(crate) fn write_header(w: &mut Writer, head: &WavHeader) -> Result<(), EncoderError> {
+    let (format_id, format_flags) = match (head.sample_format, head.bits_per_sample) {
+        (SampleFormat::Int, 8 | 16 | 24 | 32) => ("lpcm", 0),
+        (SampleFormat::Int, _) => return Err(EncoderError::UnsupportedFormat {
+            attribute: "bits per integer sample",
+            expected: &[8, 16, 24, 32],
+            found: head.bits_per_sample as u32,
+        }),
+        (SampleFormat::Float, 32 | 64) => ("lpcm", FLAG_IS_FLOAT),
+        (SampleFormat::Float, _) => return Err(EncoderError::UnsupportedFormat {
+            attribute: "bits per float sample",
+            expected: &[32, 64],
+            found: head.bits_per_sample as u32,
+        }),
+        (SampleFormat::WaveFormatMuLaw, 8) => ("ulaw", 0),
+        (SampleFormat::WaveFromatALaw, 8) => ("alaw", 0),
+        _ => return Err(EncoderError::UnsupportedEncoding),
+    };
+    w.write_str("caff");
+    w.write_u16(1); // version
+    w.write_u16(0); // flags
+    w.write_str("desc");
+    w.write_u64(32);
+    w.write_bytes(&(head.sample_rate as f64).to_be_bytes());
+    w.write_str(format_id);
+    w.write_u32(format_flags);
+    w.write_u32((head.bits_per_sample / 8) as u32 * head.channels as u32);
+    w.write_u32(1); // frames per packet
+    w.write_u32(head.channels as u32);
+    w.write_u32(head.bits_per_sample as u32);
+    // info strings from the LIST INFO items
+    let mut pairs = vec![];
+    if let Some(list) = &head.list_chunk {
+        for it in list.items.iter() {
+            if let Some((key, _)) = INFO_KEYS.iter().find(|(_, id)| *id == it.id) {
+                pairs.push((*key, it.value.as_str()));
+            }
+        }
+    }
+    if !pairs.is_empty() {
+        let size: usize = pairs.iter().map(|(k, v)| k.len() + v.len() + 2).sum();
+        w.write_str("info");
+        w.write_u64(size as u64 + 4);
+        w.write_u32(pairs.len() as u32);
+        for (key, value) in pairs {
+            w.write_bytes(key.as_bytes());
+            w.write_u8(0);
+            w.write_bytes(value.as_bytes());
+            w.write_u8(0);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn caf_roundtrip() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 24;
+        head.list_chunk = Some(ListChunk {
+            items: vec![
+                ListChunkItem { id: "INAM".to_string(), value: "take 1".to_string() },
+                ListChunkItem { id: "IART".to_string(), value: "iPhone".to_string() },
+            ],
+        });
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        let bytes = to_bytes(&head, &samples).unwrap();
+        // caff(8) + desc(12 + 32) + info(12 + 4 + 27) + data(12 + 4) + 12
+        assert_eq!(bytes.len(), 8 + 44 + 43 + 16 + 12);
+        assert_eq!(&bytes[0..8], b"caff\x00\x01\x00\x00");
+        assert_eq!(&bytes[8..12], b"desc");
+        assert_eq!(f64::from_be_bytes(bytes[20..28].try_into().unwrap()), 44_100.0);
+        assert_eq!(&bytes[28..32], b"lpcm");
+        assert_eq!(&bytes[95..99], b"data");
+        assert_eq!(u64::from_be_bytes(bytes[99..107].try_into().unwrap()), 16);
+        // big-endian signed samples
+        assert_eq!(&bytes[111..114], &[0x40, 0x00, 0x00]);
+
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Caf);
+        assert_eq!(h.channels, 2);
+        assert_eq!(h.bits_per_sample, 24);
+        assert_eq!(h.sample_format, SampleFormat::Int);
+        assert_eq!(h.list_chunk, head.list_chunk);
+        for (a, b) in r.get_samples_f32().unwrap().iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-5, "{a} != {b}");
+        }
+
+        // streaming writer patches the data size
+        let mut cur = std::io::Cursor::new(Vec::new());
+        let mut w = crate::writer::WavWriter::with_container(&mut cur, &head, ContainerKind::Caf).unwrap();
+        w.write_samples(&samples[..2]).unwrap();
+        w.write_samples(&samples[2..]).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), bytes);
+    }
+
+    #[test]
+    fn caf_little_endian_float_open_ended() {
+        // little-endian float samples, data size -1 (until the end of the file)
+        let mut data = vec![];
+        data.extend_from_slice(b"caff\x00\x01\x00\x00desc");
+        data.extend_from_slice(&32u64.to_be_bytes());
+        data.extend_from_slice(&48_000f64.to_be_bytes());
+        data.extend_from_slice(b"lpcm");
+        for v in [FLAG_IS_FLOAT | FLAG_IS_LITTLE_ENDIAN, 4, 1, 1, 32] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        data.extend_from_slice(b"free");
+        data.extend_from_slice(&3u64.to_be_bytes());
+        data.extend_from_slice(&[0; 3]);
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        for v in [0.5f32, -0.25, 1.0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let mut r = Reader::from_vec(data).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_rate, 48_000);
+        assert_eq!(h.sample_format, SampleFormat::Float);
+        assert_eq!(r.get_samples_f32().unwrap(), vec![0.5, -0.25, 1.0]);
+    }
+}
@@ -0,0 +1,1029 @@
+//! FLAC decoder and encoder
+//! (ref) https://xiph.org/flac/format.html
+//!
+//! The whole stream is decoded in memory (`reader::open` detects the `fLaC` magic).
+//! The encoder writes fixed size blocks with fixed predictors and Rice coded residuals.
+//! Vorbis comments are mapped to the items of the LIST INFO chunk.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use crate::header::*;
+use crate::reader::DecodeError;
+use crate::writer::{Writer, EncoderError};
+
+/// samples per channel in each encoded frame
+const BLOCK_SIZE: usize = 4096;
+/// highest partition order tried by the encoder
+const MAX_PARTITION_ORDER: u32 = 8;
+
+/// Vorbis comment fields and the LIST INFO ids they are mapped to
+const COMMENT_KEYS: [(&str, &str); 10] = [
+    ("TITLE", "INAM"),
+    ("ARTIST", "IART"),
+    ("ALBUM", "IPRD"),
+    ("GENRE", "IGNR"),
+    ("COMMENT", "ICMT"),
+    ("DESCRIPTION", "ICMT"),
+    ("TRACKNUMBER", "ITRK"),
+    ("DATE", "ICRD"),
+    ("COPYRIGHT", "ICOP"),
+    ("ENCODER", "ISFT"),
+];
+
+/// sample rates with a frame header code
+const SAMPLE_RATE_CODES: [(u32, u64); 11] = [
+    (88_200, 1), (176_400, 2), (192_000, 3), (8_000, 4), (16_000, 5), (22_050, 6),
+    (24_000, 7), (32_000, 8), (44_100, 9), (48_000, 10), (96_000, 11),
+];
+
+/// predictor coefficients of the fixed subframes (order 0 to 4)
+const FIXED_COEFS: [&[i64]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+/// Decode FLAC file
+pub fn from_file(mut file: File) -> Result<WavData, DecodeError> {
+    let mut data = vec![];
+    file.read_to_end(&mut data).map_err(|err| DecodeError::ReadFail { source: err })?;
+    from_bytes(&data)
+}
+
+/// Decode FLAC bytes
+/// The MD5 signature of STREAMINFO is verified when it is set
+pub fn from_bytes(data: &[u8]) -> Result<WavData, DecodeError> {
+    if data.len() < 4 || &data[0..4] != b"fLaC" {
+        return Err(DecodeError::InvalidTag {
+            expected: "fLaC",
+            found: String::from_utf8_lossy(&data[..data.len().min(4)]).to_string(),
+        });
+    }
+    // metadata blocks
+    let mut pos = 4;
+    let mut info = None;
+    let mut items = vec![];
+    loop {
+        let Some(h) = data.get(pos..pos + 4) else {
+            return Err(unexpected_eof());
+        };
+        let size = u32::from_be_bytes([0, h[1], h[2], h[3]]) as usize;
+        let Some(block) = data.get(pos + 4..pos + 4 + size) else {
+            return Err(unexpected_eof());
+        };
+        match h[0] & 0x7F {
+            0 => info = Some(StreamInfo::parse(block)?),
+            4 => read_vorbis_comment(block, &mut items),
+            _ => {},
+        }
+        pos += 4 + size;
+        if h[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    let Some(info) = info else {
+        return Err(DecodeError::InvalidTag { expected: "STREAMINFO", found: String::new() });
+    };
+    if info.channels == 0 || info.bits < 4 || info.bits > 32 {
+        return Err(DecodeError::UnsupportedWav {
+            attribute: "bits per FLAC sample",
+            expected: &[8, 12, 16, 20, 24, 32],
+            found: info.bits,
+        });
+    }
+
+    // audio frames
+    let channels = info.channels as usize;
+    let mut ints: Vec<i32> = vec![];
+    let mut r = BitReader { data, pos: pos * 8 };
+    while r.pos / 8 < data.len() {
+        if info.total_samples > 0 && ints.len() as u64 >= info.total_samples * channels as u64 {
+            break;
+        }
+        decode_frame(&mut r, &info, &mut ints)?;
+    }
+    if info.total_samples > 0 {
+        ints.truncate((info.total_samples * channels as u64).min(ints.len() as u64) as usize);
+    }
+    if info.md5 != [0; 16] {
+        let width = info.bits.div_ceil(8) as usize;
+        let mut md5 = Md5::new();
+        for v in ints.iter() {
+            md5.update(&v.to_le_bytes()[..width]);
+        }
+        if md5.finish() != info.md5 {
+            return Err(DecodeError::InvalidChecksum { attribute: "MD5 signature" });
+        }
+    }
+
+    // samples are scaled like WAV samples of the next byte width
+    let bits = info.bits.div_ceil(8) * 8;
+    let shift = bits - info.bits;
+    let scale = ((1u64 << bits) - 1) as f32 / 2.0;
+    let samples = ints.iter().map(|v| ((*v as i64) << shift) as f32 / scale).collect();
+    let mut header = WavHeader::new();
+    header.set_int_format();
+    header.channels = info.channels as u16;
+    header.sample_rate = info.sample_rate;
+    header.bits_per_sample = bits as u16;
+    if !items.is_empty() {
+        header.list_chunk = Some(ListChunk { items });
+    }
+    Ok(WavData { header, samples })
+}
+
+/// WavData to FLAC file
+pub fn to_file(file_out: &mut File, wav: &WavData) -> Result<(), EncoderError> {
+    let bytes = to_bytes(&wav.header, &wav.samples)?;
+    file_out.write_all(&bytes).map_err(|err| EncoderError::IOError { source: err })
+}
+
+/// WavData to FLAC bytes
+/// Only 8, 16 and 24-bit integer samples can be encoded
+pub fn to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, EncoderError> {
+    if head.sample_format != SampleFormat::Int {
+        return Err(EncoderError::UnsupportedEncoding);
+    }
+    if !matches!(head.bits_per_sample, 8 | 16 | 24) {
+        return Err(EncoderError::UnsupportedFormat {
+            attribute: "bits per FLAC sample",
+            expected: &[8, 16, 24],
+            found: head.bits_per_sample as u32,
+        });
+    }
+    if !(1..=8).contains(&head.channels) {
+        return Err(EncoderError::UnsupportedFormat {
+            attribute: "channels",
+            expected: &[1, 2, 3, 4, 5, 6, 7, 8],
+            found: head.channels as u32,
+        });
+    }
+    // STREAMINFO has 20 bits for the rate (1 to 1048575), the frames refer to it when the rate has no code
+    if !(1..1 << 20).contains(&head.sample_rate) {
+        return Err(EncoderError::UnsupportedFormat {
+            attribute: "sample rate",
+            expected: &[1, 1_048_575],
+            found: head.sample_rate,
+        });
+    }
+    // quantize like the WAV writer (little-endian, unsigned 8-bit)
+    let mut body = Writer::new();
+    body.write_samples(head, samples)?;
+    let bytes = body.to_bytes();
+    let bits = head.bits_per_sample as u32;
+    let mut ints: Vec<i32> = match bits {
+        8 => bytes.iter().map(|b| *b as i32 - 128).collect(),
+        16 => bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as i32).collect(),
+        _ => bytes.chunks_exact(3).map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8).collect(),
+    };
+    let channels = head.channels as usize;
+    let total_samples = (ints.len() / channels) as u64;
+    ints.truncate(total_samples as usize * channels);
+
+    let mut frames = vec![];
+    let (mut min_frame, mut max_frame) = (0, 0);
+    for (index, block) in ints.chunks(BLOCK_SIZE * channels).enumerate() {
+        let frame = encode_frame(block, channels, bits, head.sample_rate, index as u64);
+        min_frame = if index == 0 { frame.len() } else { min_frame.min(frame.len()) };
+        max_frame = max_frame.max(frame.len());
+        frames.extend_from_slice(&frame);
+    }
+    let mut md5 = Md5::new();
+    for v in ints.iter() {
+        md5.update(&v.to_le_bytes()[..(bits / 8) as usize]);
+    }
+
+    let mut w = BitWriter::new();
+    w.write_bytes(b"fLaC");
+    let comment = make_vorbis_comment(head);
+    // STREAMINFO
+    w.write(1, comment.is_none() as u64);
+    w.write(7, 0);
+    w.write(24, 34);
+    w.write(16, BLOCK_SIZE as u64);
+    w.write(16, BLOCK_SIZE as u64);
+    w.write(24, min_frame as u64);
+    w.write(24, max_frame as u64);
+    w.write(20, head.sample_rate as u64);
+    w.write(3, channels as u64 - 1);
+    w.write(5, bits as u64 - 1);
+    w.write(36, total_samples);
+    w.write_bytes(&md5.finish());
+    if let Some(comment) = comment {
+        w.write(1, 1);
+        w.write(7, 4);
+        w.write(24, comment.len() as u64);
+        w.write_bytes(&comment);
+    }
+    let mut out = w.into_bytes();
+    out.extend_from_slice(&frames);
+    Ok(out)
+}
+
+/// STREAMINFO metadata block
+struct StreamInfo {
+    sample_rate: u32,
+    channels: u32,
+    bits: u32,
+    total_samples: u64, // 0 = unknown
+    md5: [u8; 16],
+}
+
+impl StreamInfo {
+    fn parse(block: &[u8]) -> Result<Self, DecodeError> {
+        if block.len() < 34 {
+            return Err(DecodeError::InvalidChunkAttribute {
+                attribute: "STREAMINFO size",
+                expected: 33,
+                found: block.len() as u32,
+            });
+        }
+        let mut r = BitReader { data: block, pos: 0 };
+        r.read(16 + 16 + 24 + 24)?; // block and frame sizes
+        let sample_rate = r.read(20)? as u32;
+        let channels = r.read(3)? as u32 + 1;
+        let bits = r.read(5)? as u32 + 1;
+        let total_samples = r.read(36)?;
+        let mut md5 = [0; 16];
+        md5.copy_from_slice(&block[18..34]);
+        Ok(Self { sample_rate, channels, bits, total_samples, md5 })
+    }
+}
+
+/// Read the Vorbis comments (little-endian lengths and `KEY=value` strings)
+fn read_vorbis_comment(block: &[u8], items: &mut Vec<ListChunkItem>) {
+    let mut pos = 0;
+    let mut next = |len: usize| -> Option<&[u8]> {
+        let v = block.get(pos..pos + len)?;
+        pos += len;
+        Some(v)
+    };
+    let read_u32 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+    let Some(vendor_len) = next(4).map(read_u32) else { return };
+    if next(vendor_len).is_none() {
+        return;
+    }
+    let Some(count) = next(4).map(read_u32) else { return };
+    for _ in 0..count {
+        let Some(len) = next(4).map(read_u32) else { return };
+        let Some(comment) = next(len) else { return };
+        let comment = String::from_utf8_lossy(comment);
+        let Some((key, value)) = comment.split_once('=') else { continue };
+        let key = key.to_uppercase();
+        if let Some((_, id)) = COMMENT_KEYS.iter().find(|(k, _)| *k == key) {
+            items.push(ListChunkItem { id: id.to_string(), value: value.to_string() });
+        }
+    }
+}
+
+/// Make the Vorbis comment block from the LIST INFO items
+fn make_vorbis_comment(head: &WavHeader) -> Option<Vec<u8>> {
+    let list = head.list_chunk.as_ref()?;
+    let comments: Vec<String> = list.items.iter()
+        .filter_map(|it| {
+            let (key, _) = COMMENT_KEYS.iter().find(|(_, id)| *id == it.id)?;
+            Some(format!("{}={}", key, it.value))
+        })
+        .collect();
+    if comments.is_empty() {
+        return None;
+    }
+    let vendor = b"wav_io";
+    let mut block = vec![];
+    block.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    block.extend_from_slice(vendor);
+    block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for c in comments.iter() {
+        block.extend_from_slice(&(c.len() as u32).to_le_bytes());
+        block.extend_from_slice(c.as_bytes());
+    }
+    Some(block)
+}
+
+/// Decode one frame and append the interleaved samples
+fn decode_frame(r: &mut BitReader, info: &StreamInfo, out: &mut Vec<i32>) -> Result<(), DecodeError> {
+    let start = r.pos / 8;
+    if r.read(14)? != 0x3FFE {
+        return Err(DecodeError::InvalidTag { expected: "frame sync", found: String::new() });
+    }
+    r.read(2)?; // reserved, blocking strategy
+    let block_size_code = r.read(4)?;
+    let sample_rate_code = r.read(4)?;
+    let channel_code = r.read(4)? as u32;
+    let sample_size_code = r.read(3)?;
+    r.read(1)?; // reserved
+    r.read_utf8()?; // frame or sample number
+    let block_size = match block_size_code {
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => r.read(8)? + 1,
+        7 => r.read(16)? + 1,
+        8..=15 => 256 << (block_size_code - 8),
+        _ => return Err(invalid_frame("block size", block_size_code)),
+    } as usize;
+    match sample_rate_code {
+        12 => { r.read(8)?; },
+        13 | 14 => { r.read(16)?; },
+        15 => return Err(invalid_frame("sample rate", sample_rate_code)),
+        _ => {},
+    }
+    let bits = match sample_size_code {
+        0 => info.bits,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => return Err(invalid_frame("sample size", sample_size_code)),
+    };
+    let crc = crc8(&r.data[start..r.pos / 8]);
+    if r.read(8)? as u8 != crc {
+        return Err(DecodeError::InvalidChecksum { attribute: "frame header CRC-8" });
+    }
+    let channels = match channel_code {
+        0..=7 => channel_code + 1,
+        8..=10 => 2,
+        _ => return Err(invalid_frame("channel assignment", channel_code as u64)),
+    };
+    if channels != info.channels {
+        return Err(DecodeError::UnsupportedWav {
+            attribute: "frame channels",
+            expected: &[],
+            found: channels,
+        });
+    }
+    let mut subframes = vec![];
+    for ch in 0..channels {
+        // the side channel has one more bit
+        let side = matches!((channel_code, ch), (8, 1) | (9, 0) | (10, 1));
+        subframes.push(decode_subframe(r, block_size, bits + side as u32)?);
+    }
+    r.align();
+    let crc = crc16(&r.data[start..r.pos / 8]);
+    if r.read(16)? as u16 != crc {
+        return Err(DecodeError::InvalidChecksum { attribute: "frame CRC-16" });
+    }
+    // inter-channel decorrelation
+    if let [a, b] = &mut subframes[..] {
+        for (a, b) in a.iter_mut().zip(b.iter_mut()) {
+            match channel_code {
+                8 => *b = a.wrapping_sub(*b),
+                9 => *a = a.wrapping_add(*b),
+                10 => {
+                    let mid = a.wrapping_shl(1) | (*b & 1);
+                    (*a, *b) = (mid.wrapping_add(*b) >> 1, mid.wrapping_sub(*b) >> 1);
+                },
+                _ => {},
+            }
+        }
+    }
+    for i in 0..block_size {
+        for s in subframes.iter() {
+            out.push(s[i] as i32);
+        }
+    }
+    Ok(())
+}
+
+/// Decode one subframe of `n` samples
+fn decode_subframe(r: &mut BitReader, n: usize, bits: u32) -> Result<Vec<i64>, DecodeError> {
+    if r.read(1)? != 0 {
+        return Err(invalid_frame("subframe padding", 1));
+    }
+    let kind = r.read(6)?;
+    let wasted = if r.read(1)? == 1 { r.read_unary()? + 1 } else { 0 };
+    if wasted >= bits as u64 {
+        return Err(invalid_frame("wasted bits", wasted));
+    }
+    let bits = bits - wasted as u32;
+    let mut s = match kind {
+        0 => vec![r.read_signed(bits)?; n],
+        1 => (0..n).map(|_| r.read_signed(bits)).collect::<Result<Vec<_>, _>>()?,
+        8..=12 => {
+            let coefs = FIXED_COEFS[kind as usize - 8];
+            let mut s = read_warmup(r, n, coefs.len(), bits)?;
+            read_residual(r, n, coefs.len(), &mut s)?;
+            predict(&mut s, coefs, 0);
+            s
+        },
+        32..=63 => {
+            let order = (kind - 31) as usize;
+            let mut s = read_warmup(r, n, order, bits)?;
+            let precision = r.read(4)? as u32 + 1;
+            if precision == 16 {
+                return Err(invalid_frame("LPC precision", 15));
+            }
+            let shift = r.read_signed(5)?;
+            if shift < 0 {
+                return Err(invalid_frame("LPC shift", shift as u64));
+            }
+            let coefs = (0..order).map(|_| r.read_signed(precision)).collect::<Result<Vec<_>, _>>()?;
+            read_residual(r, n, order, &mut s)?;
+            predict(&mut s, &coefs, shift as u32);
+            s
+        },
+        _ => return Err(invalid_frame("subframe type", kind)),
+    };
+    if wasted > 0 {
+        for v in s.iter_mut() {
+            *v <<= wasted;
+        }
+    }
+    Ok(s)
+}
+
+/// Read the unencoded warm-up samples of a predictor
+fn read_warmup(r: &mut BitReader, n: usize, order: usize, bits: u32) -> Result<Vec<i64>, DecodeError> {
+    if order > n {
+        return Err(invalid_frame("predictor order", order as u64));
+    }
+    let mut s = Vec::with_capacity(n);
+    for _ in 0..order {
+        s.push(r.read_signed(bits)?);
+    }
+    Ok(s)
+}
+
+/// Read the Rice coded residual and append it to the warm-up samples
+fn read_residual(r: &mut BitReader, n: usize, order: usize, out: &mut Vec<i64>) -> Result<(), DecodeError> {
+    let param_bits = match r.read(2)? {
+        0 => 4,
+        1 => 5,
+        method => return Err(invalid_frame("residual coding method", method)),
+    };
+    let escape = (1 << param_bits) - 1;
+    let partition_order = r.read(4)? as u32;
+    let partition_size = n >> partition_order;
+    if partition_size << partition_order != n || partition_size < order {
+        return Err(invalid_frame("partition order", partition_order as u64));
+    }
+    for p in 0..1 << partition_order {
+        let count = if p == 0 { partition_size - order } else { partition_size };
+        let k = r.read(param_bits)?;
+        if k == escape {
+            let raw_bits = r.read(5)? as u32;
+            for _ in 0..count {
+                out.push(r.read_signed(raw_bits)?);
+            }
+        } else {
+            for _ in 0..count {
+                let u = r.read_unary()?.wrapping_shl(k as u32) | r.read(k as u32)?;
+                out.push((u >> 1) as i64 ^ -((u & 1) as i64));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Restore the samples from the residual (in place)
+fn predict(s: &mut [i64], coefs: &[i64], shift: u32) {
+    // a broken stream must not overflow
+    for i in coefs.len()..s.len() {
+        let sum = coefs.iter().enumerate().fold(0i64, |sum, (j, c)| sum.wrapping_add(c.wrapping_mul(s[i - 1 - j])));
+        s[i] = s[i].wrapping_add(sum >> shift);
+    }
+}
+
+fn invalid_frame(attribute: &'static str, found: u64) -> DecodeError {
+    DecodeError::UnsupportedWav { attribute, expected: &[], found: found as u32 }
+}
+
+fn unexpected_eof() -> DecodeError {
+    DecodeError::ReadFail { source: std::io::ErrorKind::UnexpectedEof.into() }
+}
+
+/// Encode one frame of interleaved samples
+fn encode_frame(block: &[i32], channels: usize, bits: u32, sample_rate: u32, index: u64) -> Vec<u8> {
+    let n = block.len() / channels;
+    let chans: Vec<Vec<i64>> = (0..channels)
+        .map(|c| block.iter().skip(c).step_by(channels).map(|v| *v as i64).collect())
+        .collect();
+    // stereo: pick the cheapest of left/right, left/side, side/right and mid/side
+    let (channel_code, subframes) = if channels == 2 {
+        let side: Vec<i64> = chans[0].iter().zip(chans[1].iter()).map(|(l, r)| l - r).collect();
+        let mid: Vec<i64> = chans[0].iter().zip(chans[1].iter()).map(|(l, r)| (l + r) >> 1).collect();
+        let left = encode_subframe(&chans[0], bits);
+        let right = encode_subframe(&chans[1], bits);
+        let side = encode_subframe(&side, bits + 1);
+        let mid = encode_subframe(&mid, bits);
+        let options = [(1, &left, &right), (8, &left, &side), (9, &side, &right), (10, &mid, &side)];
+        let (code, a, b) = options.into_iter()
+            .min_by_key(|(_, a, b)| a.bit_len() + b.bit_len())
+            .unwrap();
+        (code, vec![a.clone(), b.clone()])
+    } else {
+        (channels as u64 - 1, chans.iter().map(|s| encode_subframe(s, bits)).collect())
+    };
+
+    let mut w = BitWriter::new();
+    w.write(16, 0xFFF8); // sync, fixed block size
+    let (block_size_code, block_size_bits) = match n {
+        BLOCK_SIZE => (12, 0),
+        1..=256 => (6, 8),
+        _ => (7, 16),
+    };
+    w.write(4, block_size_code);
+    let sample_rate_code = SAMPLE_RATE_CODES.iter()
+        .find(|(rate, _)| *rate == sample_rate)
+        .map(|(_, code)| *code)
+        .unwrap_or(0); // from STREAMINFO
+    w.write(4, sample_rate_code);
+    w.write(4, channel_code);
+    w.write(3, match bits { 8 => 1, 16 => 4, _ => 6 });
+    w.write(1, 0);
+    w.write_utf8(index);
+    w.write(block_size_bits, n as u64 - 1);
+    let crc = crc8(&w.data);
+    w.write(8, crc as u64);
+    for s in subframes.iter() {
+        w.append(s);
+    }
+    w.align();
+    let crc = crc16(&w.data);
+    w.write(16, crc as u64);
+    w.into_bytes()
+}
+
+/// Encode one subframe (constant, fixed predictor or verbatim)
+fn encode_subframe(s: &[i64], bits: u32) -> BitWriter {
+    let mut w = BitWriter::new();
+    if s.iter().all(|v| *v == s[0]) {
+        w.write(8, 0);
+        w.write_signed(bits, s[0]);
+        return w;
+    }
+    // the fixed order with the smallest residual
+    let max_order = s.len().min(FIXED_COEFS.len() - 1);
+    let residual = |order: usize| -> Vec<i64> {
+        let coefs = FIXED_COEFS[order];
+        (order..s.len())
+            .map(|i| s[i] - coefs.iter().enumerate().map(|(j, c)| c * s[i - 1 - j]).sum::<i64>())
+            .collect()
+    };
+    let (order, res) = (0..=max_order)
+        .map(|order| (order, residual(order)))
+        .min_by_key(|(_, res)| res.iter().map(|v| v.unsigned_abs()).sum::<u64>())
+        .unwrap();
+    w.write(8, (8 + order as u64) << 1);
+    for v in s[..order].iter() {
+        w.write_signed(bits, *v);
+    }
+    write_residual(&mut w, &res, s.len(), order);
+    if w.bit_len() > 8 + s.len() * bits as usize {
+        // verbatim is smaller
+        w = BitWriter::new();
+        w.write(8, 1 << 1);
+        for v in s.iter() {
+            w.write_signed(bits, *v);
+        }
+    }
+    w
+}
+
+/// Write the residual with the partition order of the smallest estimated size
+fn write_residual(w: &mut BitWriter, res: &[i64], n: usize, order: usize) {
+    let zigzag: Vec<u64> = res.iter().map(|v| ((v << 1) ^ (v >> 63)) as u64).collect();
+    let mut best: Option<(u32, Vec<u32>, u64)> = None;
+    for partition_order in 0..=MAX_PARTITION_ORDER {
+        let partition_size = n >> partition_order;
+        if partition_size << partition_order != n || partition_size < order.max(1) {
+            break;
+        }
+        let mut params = vec![];
+        let mut cost = 0;
+        let mut start = 0;
+        for p in 0..1 << partition_order {
+            let count = if p == 0 { partition_size - order } else { partition_size };
+            let sum: u64 = zigzag[start..start + count].iter().sum();
+            start += count;
+            // Rice parameter close to log2 of the mean
+            let mean = sum / count.max(1) as u64;
+            let k = if mean == 0 { 0 } else { (63 - mean.leading_zeros()).min(30) };
+            cost += 5 + count as u64 * (k as u64 + 1) + (sum >> k);
+            params.push(k);
+        }
+        if best.as_ref().is_none_or(|(_, _, c)| cost < *c) {
+            best = Some((partition_order, params, cost));
+        }
+    }
+    let (partition_order, params, _) = best.unwrap_or((0, vec![0], 0));
+    let wide = params.iter().any(|k| *k >= 15);
+    w.write(2, wide as u64);
+    w.write(4, partition_order as u64);
+    let mut values = zigzag.iter();
+    let partition_size = n >> partition_order;
+    for (p, k) in params.iter().enumerate() {
+        w.write(if wide { 5 } else { 4 }, *k as u64);
+        let count = if p == 0 { partition_size - order } else { partition_size };
+        for u in values.by_ref().take(count) {
+            w.write_unary(u >> k);
+            w.write(*k, *u);
+        }
+    }
+}
+
+/// MSB-first bit reader
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // in bits
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, mut n: u32) -> Result<u64, DecodeError> {
+        if self.pos + n as usize > self.data.len() * 8 {
+            return Err(unexpected_eof());
+        }
+        let mut v = 0u64;
+        while n > 0 {
+            let avail = 8 - (self.pos % 8) as u32;
+            let take = avail.min(n);
+            let bits = (self.data[self.pos / 8] as u32 >> (avail - take)) & ((1 << take) - 1);
+            v = (v << take) | bits as u64;
+            self.pos += take as usize;
+            n -= take;
+        }
+        Ok(v)
+    }
+    fn read_signed(&mut self, n: u32) -> Result<i64, DecodeError> {
+        if n == 0 {
+            return Ok(0);
+        }
+        let v = self.read(n)?;
+        Ok(((v << (64 - n)) as i64) >> (64 - n))
+    }
+    /// number of 0 bits before the next 1 bit
+    fn read_unary(&mut self) -> Result<u64, DecodeError> {
+        let mut zeros = 0;
+        loop {
+            let Some(byte) = self.data.get(self.pos / 8) else {
+                return Err(unexpected_eof());
+            };
+            let bits = byte << (self.pos % 8);
+            if bits == 0 {
+                zeros += 8 - (self.pos % 8) as u64;
+                self.pos = (self.pos / 8 + 1) * 8;
+            } else {
+                let lz = bits.leading_zeros();
+                zeros += lz as u64;
+                self.pos += lz as usize + 1;
+                return Ok(zeros);
+            }
+        }
+    }
+    /// UTF-8 like coded number of up to 36 bits
+    fn read_utf8(&mut self) -> Result<u64, DecodeError> {
+        let first = self.read(8)?;
+        let n = (first as u8).leading_ones();
+        if n == 1 || n > 7 {
+            return Err(invalid_frame("frame number", first));
+        }
+        let mut v = if n == 0 { first } else { first & (0x7F >> n) };
+        for _ in 1..n {
+            let b = self.read(8)?;
+            if b & 0xC0 != 0x80 {
+                return Err(invalid_frame("frame number", b));
+            }
+            v = (v << 6) | (b & 0x3F);
+        }
+        Ok(v)
+    }
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// MSB-first bit writer
+#[derive(Clone)]
+struct BitWriter {
+    data: Vec<u8>,
+    acc: u64,
+    n: u32, // pending bits in acc (less than 8)
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { data: vec![], acc: 0, n: 0 }
+    }
+    fn write(&mut self, bits: u32, v: u64) {
+        if bits > 32 {
+            self.write(bits - 32, v >> 32);
+            self.write(32, v & 0xFFFF_FFFF);
+            return;
+        }
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (v & ((1 << bits) - 1));
+        self.n += bits;
+        while self.n >= 8 {
+            self.n -= 8;
+            self.data.push((self.acc >> self.n) as u8);
+        }
+        self.acc &= (1 << self.n) - 1;
+    }
+    fn write_signed(&mut self, bits: u32, v: i64) {
+        self.write(bits, v as u64);
+    }
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(32, 0);
+            zeros -= 32;
+        }
+        self.write(zeros as u32 + 1, 1);
+    }
+    fn write_utf8(&mut self, v: u64) {
+        if v < 0x80 {
+            self.write(8, v);
+            return;
+        }
+        let mut n = 2;
+        while n < 7 && v >= 1 << (5 * n + 1) {
+            n += 1;
+        }
+        self.write(8, (0xFF00u16 >> n) as u8 as u64 | (v >> (6 * (n - 1))));
+        for i in (0..n - 1).rev() {
+            self.write(8, 0x80 | ((v >> (6 * i)) & 0x3F));
+        }
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for b in bytes.iter() {
+            self.write(8, *b as u64);
+        }
+    }
+    fn append(&mut self, other: &BitWriter) {
+        self.write_bytes(&other.data);
+        self.write(other.n, other.acc);
+    }
+    fn bit_len(&self) -> usize {
+        self.data.len() * 8 + self.n as usize
+    }
+    fn align(&mut self) {
+        if self.n > 0 {
+            self.write(8 - self.n, 0);
+        }
+    }
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.data
+    }
+}
+
+/// CRC-8 of the frame header (polynomial x^8 + x^2 + x + 1)
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for b in data.iter() {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// CRC-16 of the frame (polynomial x^16 + x^15 + x^2 + 1)
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for b in data.iter() {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// MD5 digest of the decoded samples
+/// (ref) RFC 1321
+struct Md5 {
+    state: [u32; 4],
+    buf: Vec<u8>,
+    len: u64,
+}
+
+impl Md5 {
+    fn new() -> Self {
+        Self { state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476], buf: Vec::with_capacity(64), len: 0 }
+    }
+    fn update(&mut self, data: &[u8]) {
+        self.len += data.len() as u64;
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= 64 {
+            let buf = std::mem::take(&mut self.buf);
+            let mut blocks = buf.chunks_exact(64);
+            for block in blocks.by_ref() {
+                self.process(block);
+            }
+            self.buf = blocks.remainder().to_vec();
+        }
+    }
+    fn finish(mut self) -> [u8; 16] {
+        let bit_len = self.len.wrapping_mul(8);
+        let mut pad = vec![0x80];
+        pad.resize((119 - self.len % 64) as usize % 64 + 1, 0);
+        self.update(&pad);
+        self.update(&bit_len.to_le_bytes());
+        let mut out = [0; 16];
+        for (i, v) in self.state.iter().enumerate() {
+            out[i * 4..i * 4 + 4].copy_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+    fn process(&mut self, block: &[u8]) {
+        const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+        let m: Vec<u32> = block.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let k = ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32;
+            let f = f.wrapping_add(a).wrapping_add(k).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn md5_hex(data: &[u8]) -> String {
+        let mut md5 = Md5::new();
+        md5.update(data);
+        md5.finish().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn md5_digest() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        let long = b"12345678901234567890123456789012345678901234567890123456789012345678901234567890";
+        assert_eq!(md5_hex(long), "57edf4a22be3c955ac49da2e2107b67a");
+    }
+
+    #[test]
+    fn decode_reference_stream() {
+        // one stereo sample with a verbatim subframe and wasted bits (RFC 9639, appendix D.1)
+        let data = [
+            0x66, 0x4c, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x10, 0x00, 0x10, 0x00,
+            0x00, 0x00, 0x0f, 0x00, 0x00, 0x0f, 0x0a, 0xc4, 0x42, 0xf0, 0x00, 0x00,
+            0x00, 0x01, 0x3e, 0x84, 0xb4, 0x18, 0x07, 0xdc, 0x69, 0x03, 0x07, 0x58,
+            0x6a, 0x3d, 0xad, 0x1a, 0x2e, 0x0f, 0xff, 0xf8, 0x69, 0x18, 0x00, 0x00,
+            0xbf, 0x03, 0x58, 0xfd, 0x03, 0x12, 0x8b, 0xaa, 0x9a,
+        ];
+        let wav = from_bytes(&data).unwrap();
+        assert_eq!(wav.header.sample_rate, 44_100);
+        assert_eq!(wav.header.channels, 2);
+        assert_eq!(wav.header.bits_per_sample, 16);
+        assert_eq!(wav.samples.len(), 2);
+        let ints: Vec<i32> = wav.samples.iter().map(|v| (v * 32767.5).round() as i32).collect();
+        assert_eq!(ints, [25588, 10416]);
+    }
+
+    /// 8-bit samples of RFC 9639, appendix D.3
+    const LPC_SAMPLES: [i64; 24] = [
+        0, 79, 111, 78, 8, -61, -90, -68, -13, 42, 67, 53, 13, -27, -46, -38, -12, 14, 24, 19, 6, -4, -5, 0,
+    ];
+
+    #[test]
+    fn decode_lpc_reference_stream() {
+        // mono 8-bit LPC subframe of order 3 with an escaped residual partition (RFC 9639, appendix D.3)
+        let data = [
+            0x66, 0x4c, 0x61, 0x43, 0x80, 0x00, 0x00, 0x22, 0x10, 0x00, 0x10, 0x00, 0x00, 0x00, 0x1f, 0x00,
+            0x00, 0x1f, 0x07, 0xd0, 0x00, 0x70, 0x00, 0x00, 0x00, 0x18, 0xf8, 0xf9, 0xe3, 0x96, 0xf5, 0xcb,
+            0xcf, 0xc6, 0xdc, 0x80, 0x7f, 0x99, 0x77, 0x90, 0x6b, 0x32, 0xff, 0xf8, 0x68, 0x02, 0x00, 0x17,
+            0xe9, 0x44, 0x00, 0x4f, 0x6f, 0x31, 0x3d, 0x10, 0x47, 0xd2, 0x27, 0xcb, 0x6d, 0x09, 0x08, 0x31,
+            0x45, 0x2b, 0xdc, 0x28, 0x22, 0x22, 0x80, 0x57, 0xa3,
+        ];
+        let wav = from_bytes(&data).unwrap();
+        assert_eq!(wav.header.sample_rate, 32_000);
+        assert_eq!(wav.header.channels, 1);
+        assert_eq!(wav.header.bits_per_sample, 8);
+        let ints: Vec<i64> = wav.samples.iter().map(|v| (v * 128.0).round() as i64).collect();
+        assert_eq!(ints, LPC_SAMPLES);
+    }
+
+    #[test]
+    fn decode_lpc_wide_rice_parameters() {
+        // the subframe of D.3 with 5-bit Rice parameters: an escaped partition and one with parameter 16
+        let (coefs, shift) = ([7, -6, 2], 2);
+        let s = LPC_SAMPLES;
+        let res: Vec<i64> = (3..s.len())
+            .map(|i| s[i] - (coefs.iter().enumerate().map(|(j, c)| c * s[i - 1 - j]).sum::<i64>() >> shift))
+            .collect();
+        let mut w = BitWriter::new();
+        w.write(8, 34 << 1); // LPC of order 3
+        for v in s[..3].iter() {
+            w.write_signed(8, *v);
+        }
+        w.write(4, 3); // 4-bit coefficients
+        w.write_signed(5, shift);
+        for c in coefs {
+            w.write_signed(4, c);
+        }
+        w.write(2, 1);
+        w.write(4, 1); // two partitions of 12 samples
+        w.write(5, 31);
+        w.write(5, 8);
+        for v in res[..9].iter() {
+            w.write_signed(8, *v);
+        }
+        w.write(5, 16);
+        for v in res[9..].iter() {
+            let u = ((v << 1) ^ (v >> 63)) as u64;
+            w.write_unary(u >> 16);
+            w.write(16, u);
+        }
+        let data = w.into_bytes();
+        let mut r = BitReader { data: &data, pos: 0 };
+        assert_eq!(decode_subframe(&mut r, 24, 8).unwrap(), LPC_SAMPLES);
+        // a residual far out of range wraps around instead of overflowing
+        let mut s = vec![i64::MAX, i64::MAX, i64::MAX, i64::MAX];
+        predict(&mut s, &[2, -1], 0);
+        assert_eq!(s[2], i64::MAX.wrapping_add(i64::MAX));
+    }
+
+    /// samples as written to and read back from a WAV file
+    fn wav_roundtrip(head: &WavHeader, samples: &[f32]) -> Vec<f32> {
+        let bytes = crate::writer::to_bytes(head, samples).unwrap();
+        crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap().0.samples
+    }
+
+    #[test]
+    fn flac_roundtrip() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.list_chunk = Some(ListChunk {
+            items: vec![
+                ListChunkItem { id: "INAM".to_string(), value: "take 1".to_string() },
+                ListChunkItem { id: "ICMT".to_string(), value: "archive".to_string() },
+            ],
+        });
+        // two full blocks and a short one, correlated channels
+        let mut samples = vec![];
+        for i in 0..BLOCK_SIZE * 2 + 100 {
+            let v = (i as f32 * 0.01).sin() * 0.5;
+            samples.push(v);
+            samples.push(v * 0.9 + (i as f32 * 0.13).sin() * 0.01);
+        }
+        let bytes = to_bytes(&head, &samples).unwrap();
+        assert_eq!(&bytes[0..4], b"fLaC");
+        // compressed
+        assert!(bytes.len() < samples.len());
+
+        let (wav, container) = crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(container, ContainerKind::Flac);
+        assert_eq!(wav.header.channels, 2);
+        assert_eq!(wav.header.sample_rate, 44_100);
+        assert_eq!(wav.header.bits_per_sample, 16);
+        assert_eq!(wav.header.list_chunk, head.list_chunk);
+        // lossless
+        assert_eq!(wav.samples, wav_roundtrip(&head, &samples));
+    }
+
+    #[test]
+    fn flac_roundtrip_8_and_24_bits() {
+        // noise (verbatim subframes), silence (constant subframes) and a ramp
+        let mut seed = 1u32;
+        let mut samples = vec![];
+        for i in 0..3000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            samples.push((seed >> 16) as f32 / 32768.0 - 1.0);
+            samples.push(0.0);
+            samples.push(i as f32 / 3000.0 - 0.5);
+        }
+        for bits in [8, 24] {
+            let mut head = WavHeader::new_mono();
+            head.set_int_format();
+            head.channels = 3;
+            head.sample_rate = 12_345;
+            head.bits_per_sample = bits;
+            let wav = from_bytes(&to_bytes(&head, &samples).unwrap()).unwrap();
+            assert_eq!(wav.header.bits_per_sample, bits);
+            assert_eq!(wav.header.sample_rate, 12_345);
+            assert_eq!(wav.samples, wav_roundtrip(&head, &samples));
+        }
+        // FLAC stores integer samples only
+        assert!(to_bytes(&WavHeader::new_mono(), &samples).is_err());
+        // the sample rate must fit in the 20 bits of STREAMINFO
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        for rate in [0, 1 << 20] {
+            head.sample_rate = rate;
+            assert!(matches!(
+                to_bytes(&head, &samples),
+                Err(EncoderError::UnsupportedFormat { found, .. }) if found == rate
+            ));
+        }
+        head.sample_rate = (1 << 20) - 1;
+        assert_eq!(from_bytes(&to_bytes(&head, &samples[..30]).unwrap()).unwrap().header.sample_rate, (1 << 20) - 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! G.711 A-law and µ-law companding
+//! (ref) ITU-T G.711, Sun Microsystems g711.c
+
+/// µ-law bias
+const ULAW_BIAS: i32 = 0x84;
+/// µ-law clip level
+const ULAW_CLIP: i32 = 32635;
+/// A-law segment end points (13 bits)
+const ALAW_SEG_END: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+/// expand A-law byte to 16 bits linear sample
+pub fn alaw_to_linear(a: u8) -> i16 {
+    let a = a ^ 0x55;
+    let mut t = ((a & 0x0F) as i32) << 4;
+    let seg = ((a & 0x70) >> 4) as i32;
+    match seg {
+        0 => t += 8,
+        1 => t += 0x108,
+        _ => {
+            t += 0x108;
+            t <<= seg - 1;
+        },
+    }
+    if a & 0x80 != 0 { t as i16 } else { -t as i16 }
+}
+
+/// compress 16 bits linear sample to A-law byte
+pub fn linear_to_alaw(v: i16) -> u8 {
+    let mut pcm = (v as i32) >> 3;
+    let mask = if pcm >= 0 {
+        0xD5
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+    let seg = ALAW_SEG_END.iter().position(|end| pcm <= *end).unwrap_or(8);
+    if seg >= 8 {
+        return 0x7F ^ mask;
+    }
+    let mut aval = (seg as i32) << 4;
+    if seg < 2 {
+        aval |= (pcm >> 1) & 0x0F;
+    } else {
+        aval |= (pcm >> seg) & 0x0F;
+    }
+    (aval as u8) ^ mask
+}
+
+/// expand µ-law byte to 16 bits linear sample
+pub fn ulaw_to_linear(u: u8) -> i16 {
+    let u = !u;
+    let exponent = ((u >> 4) & 0x07) as i32;
+    let mantissa = (u & 0x0F) as i32;
+    let sample = (((mantissa << 3) + ULAW_BIAS) << exponent) - ULAW_BIAS;
+    if u & 0x80 != 0 { -sample as i16 } else { sample as i16 }
+}
+
+/// compress 16 bits linear sample to µ-law byte
+pub fn linear_to_ulaw(v: i16) -> u8 {
+    let mut pcm = v as i32;
+    let sign = if pcm < 0 {
+        pcm = -pcm;
+        0x80
+    } else {
+        0
+    };
+    pcm = pcm.min(ULAW_CLIP) + ULAW_BIAS;
+    let mut exponent = 7;
+    let mut mask = 0x4000;
+    while pcm & mask == 0 && exponent > 0 {
+        exponent -= 1;
+        mask >>= 1;
+    }
+    let mantissa = (pcm >> (exponent + 3)) & 0x0F;
+    !((sign | (exponent << 4) | mantissa) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn alaw_values() {
+        assert_eq!(alaw_to_linear(0xD5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+        assert_eq!(alaw_to_linear(0x2A), -32256);
+        assert_eq!(linear_to_alaw(0), 0xD5);
+        assert_eq!(linear_to_alaw(i16::MAX), 0xAA);
+        assert_eq!(linear_to_alaw(i16::MIN), 0x2A);
+        for a in 0..=255u8 {
+            assert_eq!(linear_to_alaw(alaw_to_linear(a)), a);
+        }
+    }
+    #[test]
+    fn ulaw_values() {
+        assert_eq!(ulaw_to_linear(0xFF), 0);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+        assert_eq!(linear_to_ulaw(0), 0xFF);
+        assert_eq!(linear_to_ulaw(i16::MAX), 0x80);
+        assert_eq!(linear_to_ulaw(i16::MIN), 0x00);
+        for u in 0..=255u8 {
+            // 0x7F is negative zero
+            if u == 0x7F { continue; }
+            assert_eq!(linear_to_ulaw(ulaw_to_linear(u)), u);
+        }
+    }
+}
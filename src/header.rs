@@ -1,4 +1,11 @@
-/// WAV file Header
+//! WAV file Header
+
+// the typed chunks of `WavHeader` (and the types of their fields)
+pub use crate::metadata::{
+    BextChunk, IxmlChunk, IxmlFields, IxmlTrack, CueChunk, CuePoint, AdtlItem, SmplChunk, SampleLoop, InstChunk,
+    AcidChunk, CartChunk, CartTimer, Id3Tag, Id3Picture, Id3Frame, WavMetadata, InfoField,
+};
+use crate::metadata::is_valid_info_id;
 
 /// Sample Rate - CD
 pub const SAMPLE_RATE_CD: u32 = 44_100;
@@ -18,9 +25,233 @@ pub enum SampleFormat {
     Float,
     WaveFromatALaw,
     WaveFormatMuLaw,
+    ImaAdpcm,
+    MsAdpcm,
     SubFormat,
 }
 
+/// Container of the audio data
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+pub enum ContainerKind {
+    #[default]
+    Riff, // RIFF WAVE (32-bit sizes)
+    Rf64, // RF64 WAVE, 64-bit sizes in the ds64 chunk
+    Bw64, // BW64 WAVE, same layout as RF64 (ITU-R BS.2088)
+    Wave64, // Sony Wave64, GUID chunk ids and 64-bit sizes
+    Rifx, // RIFF with big-endian fields and samples
+    Aiff, // big-endian AIFF
+    Aifc, // AIFF-C (compressed / float samples)
+    Caf, // Apple Core Audio Format
+    Au, // Sun/NeXT .au / .snd
+    Flac, // FLAC stream (decoded in memory by the flac module)
+}
+
+impl ContainerKind {
+    /// size of a chunk header (id and size field)
+    pub fn chunk_header_size(&self) -> u64 {
+        match self {
+            ContainerKind::Wave64 => 24,
+            ContainerKind::Caf => 12,
+            _ => 8,
+        }
+    }
+    /// RIFF style container (RIFF, RF64, BW64, RIFX or Wave64)
+    pub fn is_riff(&self) -> bool {
+        matches!(self, ContainerKind::Riff | ContainerKind::Rf64 | ContainerKind::Bw64 | ContainerKind::Rifx | ContainerKind::Wave64)
+    }
+    /// padding bytes after a chunk payload of `size` bytes
+    pub fn chunk_padding(&self, size: u64) -> u64 {
+        match self {
+            ContainerKind::Wave64 => (8 - size % 8) % 8,
+            ContainerKind::Caf | ContainerKind::Au => 0,
+            _ => size % 2,
+        }
+    }
+    /// byte order of the header fields
+    pub fn endian(&self) -> Endian {
+        match self {
+            ContainerKind::Rifx | ContainerKind::Aiff | ContainerKind::Aifc | ContainerKind::Caf | ContainerKind::Au => Endian::Big,
+            _ => Endian::Little,
+        }
+    }
+}
+
+/// Byte order of the header fields and samples
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// bytes of a 32-bit field in this byte order
+    pub fn u32_bytes(&self, v: u32) -> [u8; 4] {
+        match self {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        }
+    }
+    /// bytes of a 64-bit field in this byte order
+    pub fn u64_bytes(&self, v: u64) -> [u8; 8] {
+        match self {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        }
+    }
+    /// value of a 32-bit field in this byte order
+    pub fn u32_from(&self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes),
+        }
+    }
+}
+
+/// How the samples are stored in the data chunk
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+pub struct SampleLayout {
+    pub endian: Endian,
+    pub signed_8bit: bool, // WAV stores 8-bit samples unsigned, AIFF signed
+}
+
+/// chunk size field value meaning "the size is in the ds64 chunk"
+pub const RF64_SIZE_IN_DS64: u32 = 0xFFFFFFFF;
+
+/// Wave64 `riff` GUID
+pub const W64_RIFF_GUID: [u8; 16] = [
+    0x72, 0x69, 0x66, 0x66, 0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00,
+];
+/// Wave64 `list` GUID
+pub const W64_LIST_GUID: [u8; 16] = [
+    0x6C, 0x69, 0x73, 0x74, 0x2F, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00,
+];
+/// GUID tail of the Wave64 chunks named by a four character code (`wave`, `fmt `, `fact`, `data`, ...)
+pub const W64_GUID_TAIL: [u8; 12] = [
+    0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A,
+];
+
+/// make the Wave64 GUID of a chunk id
+/// (`LIST` and `RIFF` have their own GUIDs, 32 hex digits are read as a GUID, other ids use their first four chars)
+pub fn w64_guid(id: &str) -> [u8; 16] {
+    match id {
+        "RIFF" => return W64_RIFF_GUID,
+        "LIST" => return W64_LIST_GUID,
+        _ => {},
+    }
+    let mut guid = [0u8; 16];
+    if id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        for (i, b) in guid.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&id[i * 2..i * 2 + 2], 16).unwrap_or(0);
+        }
+        return guid;
+    }
+    let mut tag = [b' '; 4];
+    for (i, c) in id.chars().take(4).enumerate() {
+        tag[i] = u8::try_from(c).unwrap_or(b'?');
+    }
+    guid[0..4].copy_from_slice(&tag);
+    guid[4..].copy_from_slice(&W64_GUID_TAIL);
+    guid
+}
+
+/// chunk id of a Wave64 GUID (the inverse of `w64_guid`)
+pub fn w64_chunk_id(guid: &[u8; 16]) -> String {
+    if *guid == W64_RIFF_GUID {
+        return "RIFF".to_string();
+    }
+    if *guid == W64_LIST_GUID {
+        return "LIST".to_string();
+    }
+    if guid[4..] == W64_GUID_TAIL {
+        return chunk_id(&guid[0..4]);
+    }
+    guid.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// chunk id of raw bytes (one char per byte, so any id is written back unchanged)
+pub fn chunk_id(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// raw bytes of a chunk id (None unless it has four chars up to U+00FF)
+pub fn chunk_id_bytes(id: &str) -> Option<[u8; 4]> {
+    let mut bytes = [0u8; 4];
+    let mut chars = id.chars();
+    for b in bytes.iter_mut() {
+        *b = u8::try_from(chars.next()?).ok()?;
+    }
+    chars.next().is_none().then_some(bytes)
+}
+
+/// GUID tail shared by the KSDATAFORMAT_SUBTYPE_* sub formats
+/// (the first two bytes hold the format tag)
+pub const SUB_FORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// WAVE_FORMAT_EXTENSIBLE fields of the fmt chunk
+#[derive(Debug,Clone,PartialEq)]
+pub struct WavExtensible {
+    pub valid_bits_per_sample: u16,
+    pub channel_mask: u32, // speaker positions (SPEAKER_FRONT_LEFT=0x1, ...)
+    pub sub_format: [u8; 16], // GUID
+}
+
+impl WavExtensible {
+    /// create extensible fields for the header (default speaker layout)
+    pub fn new(head: &WavHeader) -> Self {
+        let tag = match head.sample_format {
+            SampleFormat::Float => 0x0003,
+            SampleFormat::WaveFromatALaw => 0x0006,
+            SampleFormat::WaveFormatMuLaw => 0x0007,
+            _ => 0x0001,
+        };
+        Self {
+            valid_bits_per_sample: head.bits_per_sample,
+            channel_mask: default_channel_mask(head.channels),
+            sub_format: Self::guid_from_tag(tag),
+        }
+    }
+    /// make the KSDATAFORMAT_SUBTYPE GUID of a format tag
+    pub fn guid_from_tag(tag: u16) -> [u8; 16] {
+        let mut guid = [0u8; 16];
+        guid[0..2].copy_from_slice(&tag.to_le_bytes());
+        guid[2..].copy_from_slice(&SUB_FORMAT_GUID_TAIL);
+        guid
+    }
+    /// format tag of the sub format (None if the GUID is not a KSDATAFORMAT_SUBTYPE)
+    pub fn sub_format_tag(&self) -> Option<u16> {
+        if self.sub_format[2..] != SUB_FORMAT_GUID_TAIL {
+            return None;
+        }
+        Some(u16::from_le_bytes([self.sub_format[0], self.sub_format[1]]))
+    }
+}
+
+/// default speaker positions for the number of channels
+pub fn default_channel_mask(channels: u16) -> u32 {
+    match channels {
+        1 => 0x4, // FC
+        2 => 0x3, // FL FR
+        3 => 0x7, // FL FR FC
+        4 => 0x33, // FL FR BL BR
+        5 => 0x37, // FL FR FC BL BR
+        6 => 0x3F, // 5.1
+        7 => 0x13F, // 6.1
+        8 => 0x63F, // 7.1
+        _ => 0,
+    }
+}
+
+/// ADPCM block layout of the fmt chunk
+#[derive(Debug,Clone,PartialEq)]
+pub struct AdpcmFormat {
+    pub block_align: u16, // bytes per block
+    pub samples_per_block: u16, // sample frames per block
+    pub coefs: Vec<(i16, i16)>, // MS ADPCM coefficient table (empty for IMA)
+}
+
 /// List Chunk Item
 #[derive(Debug,Clone,PartialEq)]
 pub struct ListChunkItem {
@@ -34,24 +265,28 @@ pub struct ListChunk {
 }
 impl ListChunk {
     pub fn make_block(&self) -> Vec<u8> {
+        self.make_block_endian(Endian::Little)
+    }
+    /// make the block with the sizes in the byte order of the container (big-endian in RIFX)
+    /// items whose id does not fit in four bytes are skipped
+    pub fn make_block_endian(&self, endian: Endian) -> Vec<u8> {
         let mut block = Vec::new();
         for it in self.items.iter() {
             // chunk tag
-            let chunk_tag_bytes = it.id.as_bytes();
-            let mut chunk_tag: [u8; 4] = [32u8; 4];
-            for (i, c) in chunk_tag_bytes.iter().enumerate() {
-                if i >= 4 { break; }
-                chunk_tag[i] = *c;
-            }
+            let Some(chunk_tag) = chunk_id_bytes(&it.id) else { continue };
             block.append(&mut chunk_tag.to_vec());
             // chunk size
             let mut flag_a = false;
             let mut chunk_size: u32 = it.value.len() as u32 + 1;
-            if chunk_size % 2 != 0 {
+            if !chunk_size.is_multiple_of(2) {
                 chunk_size += 1;
                 flag_a = true;
             }
-            block.append(&mut chunk_size.to_le_bytes().to_vec());
+            let size_bytes = match endian {
+                Endian::Little => chunk_size.to_le_bytes(),
+                Endian::Big => chunk_size.to_be_bytes(),
+            };
+            block.append(&mut size_bytes.to_vec());
             // chunk value
             let bytes = it.value.as_bytes();
             // println!("chunk_size={}::bytes={}", chunk_size, bytes.len());
@@ -63,6 +298,24 @@ impl ListChunk {
     }
 }
 
+/// Where a chunk is placed relative to the data chunk
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+pub enum ChunkPosition {
+    #[default]
+    BeforeData,
+    AfterData,
+}
+
+/// Chunk not parsed by the reader (vendor chunks, ...)
+/// The payload is kept as it is and written back by the writer (RIFF, RF64, BW64, RIFX and Wave64 only)
+/// `id` has one char per byte of the chunk id (32 hex digits for a Wave64 GUID without a four character code)
+#[derive(Debug,Clone,PartialEq)]
+pub struct UnknownChunk {
+    pub id: String,
+    pub data: Vec<u8>, // payload without the padding byte
+    pub position: ChunkPosition,
+}
+
 /// Wav file header
 #[derive(Debug,Clone,PartialEq)]
 pub struct WavHeader {
@@ -71,6 +324,23 @@ pub struct WavHeader {
     pub sample_rate: u32, // 44100Hz etc
     pub bits_per_sample: u16,
     pub list_chunk: Option<ListChunk>,
+    pub extensible: Option<WavExtensible>, // WAVE_FORMAT_EXTENSIBLE
+    pub adpcm: Option<AdpcmFormat>, // ADPCM block layout
+    pub bext: Option<BextChunk>, // Broadcast Wave Format
+    pub ixml: Option<IxmlChunk>, // production sound metadata
+    pub cue: Option<CueChunk>, // markers and their labels
+    pub smpl: Option<SmplChunk>, // sampler loops and tuning
+    pub inst: Option<InstChunk>, // instrument key and velocity ranges
+    pub acid: Option<AcidChunk>, // loop tempo and beats
+    pub cart: Option<CartChunk>, // broadcast cart (AES46)
+    pub id3: Option<Id3Tag>, // ID3v2 tag of the id3 chunk
+    pub unknown_chunks: Vec<UnknownChunk>, // chunks written back as they are
+}
+
+impl Default for WavHeader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WavHeader {
@@ -85,6 +355,17 @@ impl WavHeader {
             sample_rate: SAMPLE_RATE_CD,
             bits_per_sample: 16,
             list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
         }
     }
     pub fn new_mono_i16_radio() -> Self {
@@ -94,6 +375,17 @@ impl WavHeader {
             sample_rate: SAMPLE_RATE_AM_RADIO,
             bits_per_sample: 16,
             list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
         }
     }
     pub fn new_mono_f32_cd() -> Self {
@@ -103,6 +395,17 @@ impl WavHeader {
             sample_rate: SAMPLE_RATE_CD,
             bits_per_sample: 32,
             list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
         }
     }
     pub fn new_mono() -> Self {
@@ -112,6 +415,17 @@ impl WavHeader {
             sample_rate: 44100,
             bits_per_sample: 32,
             list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
         }
     }
     pub fn new_stereo() -> Self {
@@ -121,7 +435,44 @@ impl WavHeader {
             sample_rate: SAMPLE_RATE_CD,
             bits_per_sample: 32,
             list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
+        }
+    }
+    /// LIST INFO items merged with the ID3 tag (the LIST INFO values come first)
+    pub fn info_items(&self) -> Vec<ListChunkItem> {
+        let mut items = self.list_chunk.as_ref().map(|list| list.items.clone()).unwrap_or_default();
+        if let Some(id3) = &self.id3 {
+            for it in id3.to_list_items() {
+                if !items.iter().any(|i| i.id == it.id) {
+                    items.push(it);
+                }
+            }
         }
+        items
+    }
+    /// typed view of the LIST INFO items (the ID3 tag is not included, so `set_metadata` writes back the same items)
+    /// `WavMetadata::from_items(&head.info_items())` gives the view merged with the ID3 tag
+    pub fn metadata(&self) -> WavMetadata {
+        self.list_chunk.as_ref().map(WavMetadata::from_list_chunk).unwrap_or_default()
+    }
+    /// replace the LIST INFO items (removed when the metadata is empty)
+    /// items whose id `WavMetadata` does not take (e.g. "IAB\0" read from a file) are kept after the new items
+    pub fn set_metadata(&mut self, meta: &WavMetadata) {
+        let mut list = meta.to_list_chunk();
+        if let Some(old) = &self.list_chunk {
+            list.items.extend(old.items.iter().filter(|it| !is_valid_info_id(&it.id)).cloned());
+        }
+        self.list_chunk = if list.items.is_empty() { None } else { Some(list) };
     }
     pub fn set_int_format(&mut self) {
         self.sample_format = SampleFormat::Int;
@@ -144,3 +495,53 @@ impl WavData {
     }
 }
 
+
+/// Sample layout of headerless raw PCM data
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct RawSpec {
+    pub sample_format: SampleFormat,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub endian: Endian,
+    pub signed_8bit: bool, // 8-bit samples are -128..127 instead of 0..255
+}
+
+impl RawSpec {
+    /// little-endian integer samples
+    pub fn new(sample_rate: u32, channels: u16, bits_per_sample: u16) -> Self {
+        Self {
+            sample_format: SampleFormat::Int,
+            channels,
+            sample_rate,
+            bits_per_sample,
+            endian: Endian::Little,
+            signed_8bit: false,
+        }
+    }
+    /// WavHeader with the same format
+    pub fn header(&self) -> WavHeader {
+        WavHeader {
+            sample_format: self.sample_format,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.bits_per_sample,
+            list_chunk: None,
+            extensible: None,
+            adpcm: None,
+            bext: None,
+            ixml: None,
+            cue: None,
+            smpl: None,
+            inst: None,
+            acid: None,
+            cart: None,
+            id3: None,
+            unknown_chunks: Vec::new(),
+        }
+    }
+    /// how the samples are stored
+    pub fn layout(&self) -> SampleLayout {
+        SampleLayout { endian: self.endian, signed_8bit: self.signed_8bit }
+    }
+}
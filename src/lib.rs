@@ -6,9 +6,28 @@
 //! # Supported format
 //! - PCM 8, 16, 24, 32 bits Int
 //! - PCM 32, 64 bits Float
+//! - G.711 A-law / µ-law 8 bits
+//! - IMA ADPCM 4 bits
+//! - MS ADPCM 4 bits (read only)
+//! - WAVE_FORMAT_EXTENSIBLE (Int / Float / G.711 sub formats)
+//! - RF64 / BW64 (files larger than 4 GB)
+//! - RIFX (big-endian RIFF)
+//! - Sony Wave64 (W64)
+//! - AIFF / AIFF-C (`sowt` / `fl32` / `fl64` / G.711)
+//! - CAF (linear PCM / G.711)
+//! - Sun/NeXT .au / .snd (linear PCM / float / G.711)
+//! - FLAC (8 - 32 bits decode, 8 / 16 / 24 bits encode)
+//! - headerless raw PCM
+//! - BWF `bext` chunk (version 0 / 1 / 2)
+//! - `iXML` chunk
+//! - cue points and `LIST` `adtl` labels
+//! - `smpl` and `inst` chunks
+//! - `acid` and `cart` chunks
+//! - ID3v2 tag (`id3 ` chunk)
+//! - typed LIST INFO fields (`WavMetadata`)
 //! 
 //! # Functoins
-//! - read & write
+//! - read & write (the container is detected by `reader::open`)
 //! - resample
 //! - split by silence
 //! - make sine wave & MML (music macro language)
@@ -54,6 +73,40 @@
 //! }
 //! ```
 //! 
+//! Read Wav file block by block:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use wav_io::reader::Reader;
+//! fn main() {
+//!     let mut r = Reader::from_file(File::open("./sine.wav").unwrap()).unwrap();
+//!     let head = r.read_header().unwrap();
+//!     // 1024 frames at a time, the whole file is never loaded
+//!     for block in r.blocks(1024) {
+//!         let block = block.unwrap();
+//!         println!("{} frames", block.len() / head.channels as usize);
+//!     }
+//! }
+//! ```
+//!
+//! Write Wav file while recording:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use wav_io::writer::WavWriter;
+//! fn main() {
+//!     let head = wav_io::new_mono_header();
+//!     let file_out = File::create("./rec.wav").unwrap();
+//!     let mut w = WavWriter::new(file_out, &head).unwrap();
+//!     for _ in 0..10 {
+//!         let captured = vec![0.0f32; 4410]; // samples from the device
+//!         w.write_samples(&captured).unwrap();
+//!     }
+//!     // sizes are written here (or when `w` is dropped)
+//!     w.finalize().unwrap();
+//! }
+//! ```
+//!
 //! ## Other Example
 //! ```
 //! use std::fs::File;
@@ -84,7 +137,7 @@
 //!    let mut wav = reader::from_file(file_in).unwrap();
 //!    let mut samples = wav.samples;
 //!    if wav.header.channels >= 2 {
-//!        samples = utils::stereo_to_mono(samples);
+//!        samples = utils::to_mono(&samples, wav.header.channels);
 //!        wav.header.channels = 1;
 //!    }
 //!    let range_vec = splitter::split_samples(&mut samples, wav.header.sample_rate, &splitter::WavSplitOption::new());
@@ -113,6 +166,20 @@ pub mod splitter;
 pub mod tone;
 /// Utilities
 pub mod utils;
+/// G.711 A-law / µ-law codec
+pub mod g711;
+/// ADPCM codec
+pub mod adpcm;
+/// AIFF / AIFF-C reader and writer
+pub mod aiff;
+/// Core Audio Format (CAF) reader and writer
+pub mod caf;
+/// Sun/NeXT audio (.au / .snd) reader and writer
+pub mod au;
+/// FLAC decoder and encoder
+pub mod flac;
+/// Typed metadata chunks
+pub mod metadata;
 
 use header::*;
 use writer::to_bytes;
@@ -143,29 +210,29 @@ pub fn read_from_file(file_in: std::fs::File) -> Result<(WavHeader, Vec<f32>), r
     }
 }
 /// Write to Wav file
-pub fn write_to_file(file_out: &mut std::fs::File, header: &WavHeader, samples: &Vec<f32>) -> Result<(), writer::EncoderError> {
+pub fn write_to_file(file_out: &mut std::fs::File, header: &WavHeader, samples: &[f32]) -> Result<(), writer::EncoderError> {
     writer::f32samples_to_file(file_out, header, samples)
 }
 
 /// Write Wav data
-pub fn write_to_bytes(head: &WavHeader, samples: &Vec<f32>) -> Result<Vec<u8>, writer::EncoderError> {
+pub fn write_to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, writer::EncoderError> {
     to_bytes(head, samples)
 }
 
 /// convert i16 to f32 samples
-pub fn convert_samples_i16_to_f32(samples: &Vec<i16>) -> Vec<f32> {
+pub fn convert_samples_i16_to_f32(samples: &[i16]) -> Vec<f32> {
     let mut samples_f32 = vec![];
     for v in samples {
-        samples_f32.push(*v as f32 / std::i16::MAX as f32);
+        samples_f32.push(*v as f32 / i16::MAX as f32);
     }
     samples_f32
 }
 
 /// convert f32 to i16 samples
-pub fn convert_samples_f32_to_i16(samples: &Vec<f32>) -> Vec<i16> {
+pub fn convert_samples_f32_to_i16(samples: &[f32]) -> Vec<i16> {
     let mut samples_i16 = vec![];
     for v in samples {
-        samples_i16.push((*v * std::i16::MAX as f32) as i16);
+        samples_i16.push((*v * i16::MAX as f32) as i16);
     }
     samples_i16
 }
@@ -251,6 +318,23 @@ mod tests {
         assert_eq!(wav.header.sample_rate, new_sample_rate); // sample_rate
     }
 
+    #[test]
+    fn resample_multichannel() {
+        // 4 channels, each one a different constant level
+        let mut header = WavHeader::new_mono();
+        header.channels = 4;
+        let mut samples = vec![];
+        for _ in 0..1_000 {
+            samples.extend_from_slice(&[0.1, 0.2, 0.3, 0.4]);
+        }
+        let samples2 = resample::linear(samples, header.channels, 8_000, 16_000);
+        assert_eq!(samples2.len(), 8_000);
+        let channels = utils::deinterleave(&samples2, header.channels);
+        for (ch, level) in channels.iter().zip([0.1, 0.2, 0.3, 0.4]) {
+            assert!(ch.iter().all(|v| (v - level).abs() < 1e-5));
+        }
+    }
+
     #[test]
     fn split() {
         // split
@@ -272,7 +356,7 @@ mod tests {
             samples = utils::stereo_to_mono(samples);
             wav.header.channels = 1;
         }
-        assert_eq!(samples.len() > 100, true);
+        assert!(samples.len() > 100);
         // split
         let opt = splitter::WavSplitOption::new();
         let range_vec = splitter::split_samples(&mut samples, wav.header.sample_rate, &opt);
@@ -5,6 +5,13 @@ pub mod utils;
 pub mod resample;
 pub mod splitter;
 pub mod tone;
+pub mod g711;
+pub mod adpcm;
+pub mod aiff;
+pub mod caf;
+pub mod au;
+pub mod flac;
+pub mod metadata;
 
 #[derive(Debug,Clone,PartialEq)]
 pub struct CommandOpt {
@@ -27,7 +34,7 @@ fn main() {
         if i == 0 { continue; }
         // command
         if i == 1 {
-            cmd.command = String::from(s);
+            cmd.command = s;
             continue;
         }
         // debug
@@ -36,17 +43,17 @@ fn main() {
             continue;
         }
         // filename
-        if cmd.filename == None {
-            cmd.filename = Some(String::from(s));
+        if cmd.filename.is_none() {
+            cmd.filename = Some(s);
             continue;
         }
         // arg
-        if cmd.arg1 == None {
-            cmd.arg1 = Some(String::from(s));
+        if cmd.arg1.is_none() {
+            cmd.arg1 = Some(s);
             continue;
         }
-        if cmd.arg2 == None {
-            cmd.arg2 = Some(String::from(s));
+        if cmd.arg2.is_none() {
+            cmd.arg2 = Some(s);
             continue;
         }
     }
@@ -72,17 +79,18 @@ fn command_info(cmd: CommandOpt) {
     if cmd.is_debug {
         println!("{:?}", cmd);
     }
-    if cmd.filename == None {
+    if cmd.filename.is_none() {
         println!("[Usage] wav_io info [file]");
         return;
     }
-    let wav = match reader::from_file_str(&cmd.filename.unwrap()) {
+    let (wav, container) = match reader::open(cmd.filename.unwrap()) {
         Ok(data) => data,
         Err(e) => {
             eprintln!("[Error] {}", e);
             return;
         }
     };
+    println!("container={:?}", container);
     println!("header={:?}", wav.header);
     println!("samples.len={}", wav.samples.len());
  }
@@ -91,7 +99,7 @@ fn command_info(cmd: CommandOpt) {
     if cmd.is_debug {
         println!("{:?}", cmd);
     }
-    if cmd.filename == None || cmd.arg1 == None {
+    if cmd.filename.is_none() || cmd.arg1.is_none() {
         println!("[Usage] wav_io mml [file] [mml]");
         return;
     }
@@ -112,7 +120,7 @@ fn command_info(cmd: CommandOpt) {
     if cmd.is_debug {
         println!("{:?}", cmd);
     }
-    if cmd.filename == None || cmd.arg1 == None {
+    if cmd.filename.is_none() || cmd.arg1.is_none() {
         println!("[Usage] wav_io split [file] [outdir]");
         return;
     }
@@ -136,12 +144,29 @@ fn command_info(cmd: CommandOpt) {
     // convert to mono
     let mut samples = wav.samples;
     if wav.header.channels >= 2 {
-        samples = utils::stereo_to_mono(samples);
+        samples = utils::to_mono(&samples, wav.header.channels);
         wav.header.channels = 1;
     }
-    // split
-    let opt = splitter::WavSplitOption::new();
-    let range_vec = splitter::split_samples(&mut samples, wav.header.sample_rate, &opt);
+    // split by the markers or by silence
+    let range_vec = match wav.header.cue.take() {
+        Some(cue) if !cue.points.is_empty() => splitter::split_by_cues(&cue, samples.len()),
+        _ => {
+            let opt = splitter::WavSplitOption::new();
+            splitter::split_samples(&mut samples, wav.header.sample_rate, &opt)
+        },
+    };
+    // the time reference, loops, markers and vendor chunks belong to the whole file, not to a fragment
+    let fragment_header = header::WavHeader {
+        bext: None,
+        ixml: None,
+        smpl: None,
+        inst: None,
+        acid: None,
+        cart: None,
+        id3: None,
+        unknown_chunks: vec![],
+        ..wav.header
+    };
     let mut json = String::from("[");
     // save to dir
     for (i, range) in range_vec.iter().enumerate() {
@@ -150,7 +175,7 @@ fn command_info(cmd: CommandOpt) {
         println!("- {}", fname);
         let mut file_out = std::fs::File::create(&fname).unwrap();
         let sub = splitter::sub_samples(&samples, *range);
-        let wav = header::WavData{header: wav.header.clone(), samples: sub};
+        let wav = header::WavData{header: fragment_header.clone(), samples: sub};
         writer::to_file(&mut file_out, &wav).unwrap();
         // add to object
         json += &format!("{{ \"no\":{}, \"file\":\"{}\", \"start\":{}, \"end\":{} }}", i, name, range.start, range.end);
@@ -167,7 +192,7 @@ fn command_resample(cmd: CommandOpt) {
     if cmd.is_debug {
         println!("{:?}", cmd);
     }
-    if cmd.filename == None || cmd.arg1 == None || cmd.arg2 == None {
+    if cmd.filename.is_none() || cmd.arg1.is_none() || cmd.arg2.is_none() {
         println!("[Usage] wav_io resample [in] [rate] [out]");
         return;
     }
@@ -183,7 +208,7 @@ fn command_resample(cmd: CommandOpt) {
     let file_in = std::fs::File::open(infile).unwrap();
     let mut wav = reader::from_file(file_in).unwrap();
     let mut file_out = std::fs::File::create(outfile).unwrap();
-    if wav.samples.len() == 0 {
+    if wav.samples.is_empty() {
         println!("[Error] resample failed.");
         return;
     }
@@ -201,7 +226,7 @@ fn show_help() {
     println!("wav_io help                  show help");
     println!("wav_io info [file]           show file info");
     println!("wav_io mml [file] [mml]      write melody by mml");
-    println!("wav_io split [file] [outdir] split wav by silence");
+    println!("wav_io split [file] [outdir] split wav by markers or silence");
     println!("wav_io resample [in] [rate] [out] resample av file");
 }
 
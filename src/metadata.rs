@@ -0,0 +1,1725 @@
+//! Typed metadata chunks (bext, iXML, cue, smpl, inst, acid, cart, ID3, ...)
+//! and a typed view of the LIST INFO items (`WavMetadata`)
+//!
+//! The chunks are parsed by `reader::Reader` into the fields of `WavHeader`
+//! and written back by `writer::Writer` (RIFF style containers only).
+
+use crate::header::{Endian, ListChunk, ListChunkItem, chunk_id, chunk_id_bytes};
+use thiserror::Error;
+
+/// Broadcast Wave Format `bext` chunk
+/// (ref) EBU Tech 3285 version 2
+#[derive(Debug,Clone,PartialEq)]
+pub struct BextChunk {
+    pub description: String, // 256 bytes
+    pub originator: String, // 32 bytes
+    pub originator_reference: String, // 32 bytes
+    pub origination_date: String, // yyyy:mm:dd
+    pub origination_time: String, // hh:mm:ss
+    pub time_reference: u64, // first sample count since midnight
+    pub version: u16, // 0, 1 (UMID) or 2 (loudness)
+    pub umid: [u8; 64], // SMPTE UMID (version 1 and later)
+    pub loudness_value: i16, // integrated loudness in LUFS x 100 (version 2)
+    pub loudness_range: i16, // LU x 100 (version 2)
+    pub max_true_peak_level: i16, // dBTP x 100 (version 2)
+    pub max_momentary_loudness: i16, // LUFS x 100 (version 2)
+    pub max_short_term_loudness: i16, // LUFS x 100 (version 2)
+    pub coding_history: String,
+}
+
+/// size of the bext fields before the coding history
+const BEXT_FIXED_SIZE: usize = 602;
+
+impl Default for BextChunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BextChunk {
+    /// empty version 2 chunk
+    pub fn new() -> Self {
+        Self {
+            description: String::new(),
+            originator: String::new(),
+            originator_reference: String::new(),
+            origination_date: String::new(),
+            origination_time: String::new(),
+            time_reference: 0,
+            version: 2,
+            umid: [0; 64],
+            loudness_value: 0,
+            loudness_range: 0,
+            max_true_peak_level: 0,
+            max_momentary_loudness: 0,
+            max_short_term_loudness: 0,
+            coding_history: String::new(),
+        }
+    }
+    /// parse the chunk payload (None if it is too short)
+    /// The UMID and loudness fields are read only when the version has them
+    pub fn parse(data: &[u8], endian: Endian) -> Option<Self> {
+        if data.len() < BEXT_FIXED_SIZE {
+            return None;
+        }
+        let version = u16::from_le_bytes(field(data, 346, endian));
+        let mut bext = Self {
+            description: text(&data[0..256]),
+            originator: text(&data[256..288]),
+            originator_reference: text(&data[288..320]),
+            origination_date: text(&data[320..330]),
+            origination_time: text(&data[330..338]),
+            time_reference: u32::from_le_bytes(field(data, 338, endian)) as u64
+                | (u32::from_le_bytes(field(data, 342, endian)) as u64) << 32,
+            version,
+            coding_history: text(&data[BEXT_FIXED_SIZE..]),
+            ..Self::new()
+        };
+        if version >= 1 {
+            bext.umid.copy_from_slice(&data[348..412]);
+        }
+        if version >= 2 {
+            let loudness = |pos| i16::from_le_bytes(field(data, pos, endian));
+            bext.loudness_value = loudness(412);
+            bext.loudness_range = loudness(414);
+            bext.max_true_peak_level = loudness(416);
+            bext.max_momentary_loudness = loudness(418);
+            bext.max_short_term_loudness = loudness(420);
+        }
+        Some(bext)
+    }
+    /// make the chunk payload (text fields are cut to their size)
+    pub fn make_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = Vec::with_capacity(BEXT_FIXED_SIZE + self.coding_history.len());
+        put_text(&mut block, &self.description, 256);
+        put_text(&mut block, &self.originator, 32);
+        put_text(&mut block, &self.originator_reference, 32);
+        put_text(&mut block, &self.origination_date, 10);
+        put_text(&mut block, &self.origination_time, 8);
+        put_field(&mut block, (self.time_reference as u32).to_le_bytes(), endian);
+        put_field(&mut block, ((self.time_reference >> 32) as u32).to_le_bytes(), endian);
+        put_field(&mut block, self.version.to_le_bytes(), endian);
+        if self.version >= 1 {
+            block.extend_from_slice(&self.umid);
+        } else {
+            block.extend_from_slice(&[0; 64]);
+        }
+        for v in [
+            self.loudness_value,
+            self.loudness_range,
+            self.max_true_peak_level,
+            self.max_momentary_loudness,
+            self.max_short_term_loudness,
+        ] {
+            put_field(&mut block, if self.version >= 2 { v.to_le_bytes() } else { [0; 2] }, endian);
+        }
+        block.resize(BEXT_FIXED_SIZE, 0); // reserved
+        block.extend_from_slice(self.coding_history.as_bytes());
+        block
+    }
+}
+
+/// `iXML` chunk (XML document of production sound recorders)
+/// (ref) http://www.gallery.co.uk/ixml/
+///
+/// The document is written back as it is, `fields` gives a parsed view of the common fields.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct IxmlChunk {
+    pub xml: String,
+}
+
+/// Common fields of an iXML document
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct IxmlFields {
+    pub project: Option<String>,
+    pub scene: Option<String>,
+    pub take: Option<String>,
+    pub tape: Option<String>,
+    pub note: Option<String>,
+    pub tracks: Vec<IxmlTrack>,
+}
+
+/// Track of the iXML `TRACK_LIST`
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct IxmlTrack {
+    pub channel_index: u16, // 1 = first channel
+    pub interleave_index: u16, // 1 = first channel of the file
+    pub name: String,
+    pub function: String,
+}
+
+impl IxmlChunk {
+    /// parse the chunk payload (trailing null bytes are dropped)
+    pub fn parse(data: &[u8]) -> Self {
+        let end = data.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        Self { xml: String::from_utf8_lossy(&data[..end]).to_string() }
+    }
+    /// parsed view of the common fields
+    pub fn fields(&self) -> IxmlFields {
+        let value = |tag| xml_content(&self.xml, tag).map(xml_unescape);
+        let mut tracks = vec![];
+        let mut rest = xml_content(&self.xml, "TRACK_LIST").unwrap_or("");
+        while let Some((track, end)) = xml_element(rest, "TRACK") {
+            let value = |tag| xml_content(track, tag).map(xml_unescape).unwrap_or_default();
+            tracks.push(IxmlTrack {
+                channel_index: value("CHANNEL_INDEX").trim().parse().unwrap_or(0),
+                interleave_index: value("INTERLEAVE_INDEX").trim().parse().unwrap_or(0),
+                name: value("NAME"),
+                function: value("FUNCTION"),
+            });
+            rest = &rest[end..];
+        }
+        IxmlFields {
+            project: value("PROJECT"),
+            scene: value("SCENE"),
+            take: value("TAKE"),
+            tape: value("TAPE"),
+            note: value("NOTE"),
+            tracks,
+        }
+    }
+    /// make a minimal iXML document from the fields
+    pub fn from_fields(fields: &IxmlFields) -> Self {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<BWFXML>\n<IXML_VERSION>1.61</IXML_VERSION>\n");
+        for (tag, value) in [
+            ("PROJECT", &fields.project),
+            ("SCENE", &fields.scene),
+            ("TAKE", &fields.take),
+            ("TAPE", &fields.tape),
+            ("NOTE", &fields.note),
+        ] {
+            if let Some(v) = value {
+                xml.push_str(&format!("<{tag}>{}</{tag}>\n", xml_escape(v)));
+            }
+        }
+        if !fields.tracks.is_empty() {
+            xml.push_str(&format!("<TRACK_LIST>\n<TRACK_COUNT>{}</TRACK_COUNT>\n", fields.tracks.len()));
+            for t in fields.tracks.iter() {
+                xml.push_str(&format!(
+                    "<TRACK><CHANNEL_INDEX>{}</CHANNEL_INDEX><INTERLEAVE_INDEX>{}</INTERLEAVE_INDEX><NAME>{}</NAME><FUNCTION>{}</FUNCTION></TRACK>\n",
+                    t.channel_index, t.interleave_index, xml_escape(&t.name), xml_escape(&t.function),
+                ));
+            }
+            xml.push_str("</TRACK_LIST>\n");
+        }
+        xml.push_str("</BWFXML>\n");
+        Self { xml }
+    }
+}
+
+/// `cue ` chunk with the associated data list (`LIST` `adtl`)
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct CueChunk {
+    pub points: Vec<CuePoint>,
+    pub adtl: Vec<AdtlItem>, // labels, notes and regions of the cue points
+}
+
+/// Cue point (marker)
+#[derive(Debug,Clone,PartialEq)]
+pub struct CuePoint {
+    pub id: u32,
+    pub position: u32, // position in the playlist (usually the sample offset)
+    pub data_chunk_id: String, // "data"
+    pub chunk_start: u32,
+    pub block_start: u32,
+    pub sample_offset: u32, // in sample frames
+}
+
+/// Item of the associated data list
+#[derive(Debug,Clone,PartialEq)]
+pub enum AdtlItem {
+    /// `labl`: title of the cue point
+    Label { cue_id: u32, text: String },
+    /// `note`: comment of the cue point
+    Note { cue_id: u32, text: String },
+    /// `ltxt`: text of a region starting at the cue point
+    LabeledText {
+        cue_id: u32,
+        sample_length: u32, // length of the region in sample frames
+        purpose: String, // "rgn " etc
+        country: u16,
+        language: u16,
+        dialect: u16,
+        code_page: u16,
+        text: String,
+    },
+    /// other items (`file`, ...) with the payload kept as it is
+    Other { id: String, data: Vec<u8> },
+}
+
+impl AdtlItem {
+    /// id of the cue point the item belongs to (None for `Other`)
+    pub fn cue_id(&self) -> Option<u32> {
+        match self {
+            AdtlItem::Label { cue_id, .. } | AdtlItem::Note { cue_id, .. } | AdtlItem::LabeledText { cue_id, .. } => Some(*cue_id),
+            AdtlItem::Other { .. } => None,
+        }
+    }
+}
+
+impl CueChunk {
+    /// add a marker at `sample_offset` with a label, returns its id
+    pub fn add_marker(&mut self, sample_offset: u32, label: &str) -> u32 {
+        let id = self.points.iter().map(|p| p.id).max().map_or(1, |id| id + 1);
+        self.points.push(CuePoint {
+            id,
+            position: sample_offset,
+            data_chunk_id: "data".to_string(),
+            chunk_start: 0,
+            block_start: 0,
+            sample_offset,
+        });
+        if !label.is_empty() {
+            self.adtl.push(AdtlItem::Label { cue_id: id, text: label.to_string() });
+        }
+        id
+    }
+    /// label (`labl`) of the cue point
+    pub fn label(&self, cue_id: u32) -> Option<&str> {
+        self.adtl.iter().find_map(|it| match it {
+            AdtlItem::Label { cue_id: id, text } if *id == cue_id => Some(text.as_str()),
+            _ => None,
+        })
+    }
+    /// parse the payload of the `cue ` chunk
+    pub fn parse_points(data: &[u8], endian: Endian) -> Option<Vec<CuePoint>> {
+        if data.len() < 4 {
+            return None;
+        }
+        let count = u32::from_le_bytes(field(data, 0, endian)) as usize;
+        if data.len() < 4 + count.checked_mul(24)? {
+            return None;
+        }
+        let points = data[4..4 + count * 24].chunks_exact(24).map(|p| CuePoint {
+            id: u32::from_le_bytes(field(p, 0, endian)),
+            position: u32::from_le_bytes(field(p, 4, endian)),
+            data_chunk_id: String::from_utf8_lossy(&p[8..12]).to_string(),
+            chunk_start: u32::from_le_bytes(field(p, 12, endian)),
+            block_start: u32::from_le_bytes(field(p, 16, endian)),
+            sample_offset: u32::from_le_bytes(field(p, 20, endian)),
+        }).collect();
+        Some(points)
+    }
+    /// parse the payload of the `LIST` chunk of type `adtl`
+    pub fn parse_adtl(data: &[u8], endian: Endian) -> Option<Vec<AdtlItem>> {
+        if !data.starts_with(b"adtl") {
+            return None;
+        }
+        let mut items = vec![];
+        let mut pos = 4;
+        while pos + 8 <= data.len() {
+            let id = &data[pos..pos + 4];
+            let size = u32::from_le_bytes(field(data, pos + 4, endian)) as usize;
+            let Some(body) = data.get(pos + 8..pos + 8 + size) else { break };
+            pos += 8 + size + size % 2;
+            let cue_id = if body.len() >= 4 { u32::from_le_bytes(field(body, 0, endian)) } else { 0 };
+            let item = match id {
+                b"labl" if body.len() >= 4 => AdtlItem::Label { cue_id, text: text(&body[4..]) },
+                b"note" if body.len() >= 4 => AdtlItem::Note { cue_id, text: text(&body[4..]) },
+                b"ltxt" if body.len() >= 20 => AdtlItem::LabeledText {
+                    cue_id,
+                    sample_length: u32::from_le_bytes(field(body, 4, endian)),
+                    purpose: String::from_utf8_lossy(&body[8..12]).to_string(),
+                    country: u16::from_le_bytes(field(body, 12, endian)),
+                    language: u16::from_le_bytes(field(body, 14, endian)),
+                    dialect: u16::from_le_bytes(field(body, 16, endian)),
+                    code_page: u16::from_le_bytes(field(body, 18, endian)),
+                    text: text(&body[20..]),
+                },
+                _ => AdtlItem::Other { id: chunk_id(id), data: body.to_vec() },
+            };
+            items.push(item);
+        }
+        // a list without any known item is kept as an unknown chunk
+        if items.iter().all(|it| matches!(it, AdtlItem::Other { .. })) {
+            return None;
+        }
+        Some(items)
+    }
+    /// make the payload of the `cue ` chunk
+    pub fn make_points_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = Vec::with_capacity(4 + self.points.len() * 24);
+        put_field(&mut block, (self.points.len() as u32).to_le_bytes(), endian);
+        for p in self.points.iter() {
+            put_field(&mut block, p.id.to_le_bytes(), endian);
+            put_field(&mut block, p.position.to_le_bytes(), endian);
+            put_text(&mut block, &p.data_chunk_id, 4);
+            put_field(&mut block, p.chunk_start.to_le_bytes(), endian);
+            put_field(&mut block, p.block_start.to_le_bytes(), endian);
+            put_field(&mut block, p.sample_offset.to_le_bytes(), endian);
+        }
+        block
+    }
+    /// make the payload of the `LIST` chunk of type `adtl`
+    pub fn make_adtl_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = b"adtl".to_vec();
+        for it in self.adtl.iter() {
+            let mut body = vec![];
+            if let Some(cue_id) = it.cue_id() {
+                put_field(&mut body, cue_id.to_le_bytes(), endian);
+            }
+            let (id, text) = match it {
+                AdtlItem::Label { text, .. } => (*b"labl", Some(text)),
+                AdtlItem::Note { text, .. } => (*b"note", Some(text)),
+                AdtlItem::LabeledText { sample_length, purpose, country, language, dialect, code_page, text, .. } => {
+                    put_field(&mut body, sample_length.to_le_bytes(), endian);
+                    put_text(&mut body, purpose, 4);
+                    for v in [country, language, dialect, code_page] {
+                        put_field(&mut body, v.to_le_bytes(), endian);
+                    }
+                    (*b"ltxt", Some(text))
+                },
+                // skipped when the id does not fit in four bytes
+                AdtlItem::Other { id, data } => {
+                    let Some(id) = chunk_id_bytes(id) else { continue };
+                    body.extend_from_slice(data);
+                    (id, None)
+                },
+            };
+            if let Some(text) = text {
+                body.extend_from_slice(text.as_bytes());
+                body.push(0);
+            }
+            block.extend_from_slice(&id);
+            put_field(&mut block, (body.len() as u32).to_le_bytes(), endian);
+            block.extend_from_slice(&body);
+            if body.len() % 2 == 1 {
+                block.push(0);
+            }
+        }
+        block
+    }
+}
+
+/// Sampler `smpl` chunk (MIDI note, tuning and loop points)
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct SmplChunk {
+    pub manufacturer: u32, // MIDI manufacturer code (0 = none)
+    pub product: u32,
+    pub sample_period: u32, // nanoseconds per sample (1e9 / sample rate)
+    pub midi_unity_note: u32, // 60 = middle C
+    pub midi_pitch_fraction: u32, // fine tune above the unity note (0x80000000 = 50 cents)
+    pub smpte_format: u32, // 0, 24, 25, 29 or 30 frames per second
+    pub smpte_offset: u32, // 0xhhmmssff
+    pub loops: Vec<SampleLoop>,
+    pub sampler_data: Vec<u8>, // manufacturer specific data
+}
+
+/// Loop of the `smpl` chunk
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct SampleLoop {
+    pub cue_point_id: u32,
+    pub loop_type: u32, // 0 = forward, 1 = alternating, 2 = backward
+    pub start: u32, // first sample frame of the loop
+    pub end: u32, // last sample frame of the loop (played)
+    pub fraction: u32,
+    pub play_count: u32, // 0 = infinite
+}
+
+/// size of the smpl fields before the loops
+const SMPL_FIXED_SIZE: usize = 36;
+
+impl SmplChunk {
+    /// empty chunk for the sample rate with the unity note at middle C
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_period: 1_000_000_000u32.checked_div(sample_rate).unwrap_or(0),
+            midi_unity_note: 60,
+            ..Self::default()
+        }
+    }
+    /// fine tune in cents
+    pub fn pitch_cents(&self) -> f32 {
+        self.midi_pitch_fraction as f32 / 0x8000_0000u32 as f32 * 50.0
+    }
+    /// parse the chunk payload (None if it is too short)
+    pub fn parse(data: &[u8], endian: Endian) -> Option<Self> {
+        if data.len() < SMPL_FIXED_SIZE {
+            return None;
+        }
+        let value = |pos| u32::from_le_bytes(field(data, pos, endian));
+        let num_loops = value(28) as usize;
+        let loops_end = SMPL_FIXED_SIZE.checked_add(num_loops.checked_mul(24)?)?;
+        if data.len() < loops_end {
+            return None;
+        }
+        let loops = (0..num_loops).map(|i| {
+            let pos = SMPL_FIXED_SIZE + i * 24;
+            SampleLoop {
+                cue_point_id: value(pos),
+                loop_type: value(pos + 4),
+                start: value(pos + 8),
+                end: value(pos + 12),
+                fraction: value(pos + 16),
+                play_count: value(pos + 20),
+            }
+        }).collect();
+        let sampler_size = value(32) as usize;
+        let sampler_data = data[loops_end..].get(..sampler_size).unwrap_or(&data[loops_end..]).to_vec();
+        Some(Self {
+            manufacturer: value(0),
+            product: value(4),
+            sample_period: value(8),
+            midi_unity_note: value(12),
+            midi_pitch_fraction: value(16),
+            smpte_format: value(20),
+            smpte_offset: value(24),
+            loops,
+            sampler_data,
+        })
+    }
+    /// make the chunk payload
+    pub fn make_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = Vec::with_capacity(SMPL_FIXED_SIZE + self.loops.len() * 24 + self.sampler_data.len());
+        for v in [
+            self.manufacturer,
+            self.product,
+            self.sample_period,
+            self.midi_unity_note,
+            self.midi_pitch_fraction,
+            self.smpte_format,
+            self.smpte_offset,
+            self.loops.len() as u32,
+            self.sampler_data.len() as u32,
+        ] {
+            put_field(&mut block, v.to_le_bytes(), endian);
+        }
+        for lp in self.loops.iter() {
+            for v in [lp.cue_point_id, lp.loop_type, lp.start, lp.end, lp.fraction, lp.play_count] {
+                put_field(&mut block, v.to_le_bytes(), endian);
+            }
+        }
+        block.extend_from_slice(&self.sampler_data);
+        block
+    }
+}
+
+/// Instrument `inst` chunk (key and velocity ranges)
+#[derive(Debug,Clone,PartialEq)]
+pub struct InstChunk {
+    pub unshifted_note: u8, // MIDI note of the recorded pitch
+    pub fine_tune: i8, // cents (-50 to 50)
+    pub gain: i8, // dB
+    pub low_note: u8,
+    pub high_note: u8,
+    pub low_velocity: u8,
+    pub high_velocity: u8,
+}
+
+impl Default for InstChunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstChunk {
+    /// middle C over the full key and velocity ranges
+    pub fn new() -> Self {
+        Self {
+            unshifted_note: 60,
+            fine_tune: 0,
+            gain: 0,
+            low_note: 0,
+            high_note: 127,
+            low_velocity: 1,
+            high_velocity: 127,
+        }
+    }
+    /// parse the chunk payload (None if it is too short)
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let [unshifted_note, fine_tune, gain, low_note, high_note, low_velocity, high_velocity] = *data.get(..7)? else {
+            return None;
+        };
+        Some(Self {
+            unshifted_note,
+            fine_tune: fine_tune as i8,
+            gain: gain as i8,
+            low_note,
+            high_note,
+            low_velocity,
+            high_velocity,
+        })
+    }
+    /// make the chunk payload (7 bytes, the pad byte is added by the writer)
+    pub fn make_block(&self) -> Vec<u8> {
+        vec![
+            self.unshifted_note,
+            self.fine_tune as u8,
+            self.gain as u8,
+            self.low_note,
+            self.high_note,
+            self.low_velocity,
+            self.high_velocity,
+        ]
+    }
+}
+
+/// ACID loop `acid` chunk (tempo and beats)
+#[derive(Debug,Clone,PartialEq)]
+pub struct AcidChunk {
+    pub flags: u32, // ACID_* bits
+    pub root_note: u16, // MIDI note (60 = C4)
+    pub reserved1: u16,
+    pub reserved2: f32,
+    pub beats: u32,
+    pub meter_denominator: u16,
+    pub meter_numerator: u16,
+    pub tempo: f32, // beats per minute
+}
+
+/// the file is a one-shot (not a loop)
+pub const ACID_ONE_SHOT: u32 = 0x01;
+/// the root note is valid
+pub const ACID_ROOT_NOTE: u32 = 0x02;
+/// the loop is stretched to the project tempo
+pub const ACID_STRETCH: u32 = 0x04;
+/// the file is played from the disk
+pub const ACID_DISK_BASED: u32 = 0x08;
+
+/// size of the acid chunk
+const ACID_SIZE: usize = 24;
+
+impl Default for AcidChunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AcidChunk {
+    /// stretched 4/4 loop at 120 bpm
+    pub fn new() -> Self {
+        Self {
+            flags: ACID_STRETCH,
+            root_note: 60,
+            reserved1: 0x8000,
+            reserved2: 0.0,
+            beats: 4,
+            meter_denominator: 4,
+            meter_numerator: 4,
+            tempo: 120.0,
+        }
+    }
+    pub fn is_one_shot(&self) -> bool {
+        self.flags & ACID_ONE_SHOT != 0
+    }
+    pub fn set_one_shot(&mut self, one_shot: bool) {
+        if one_shot { self.flags |= ACID_ONE_SHOT; } else { self.flags &= !ACID_ONE_SHOT; }
+    }
+    /// root note when the flag is set
+    pub fn root(&self) -> Option<u16> {
+        if self.flags & ACID_ROOT_NOTE != 0 { Some(self.root_note) } else { None }
+    }
+    /// parse the chunk payload (None if it is too short)
+    pub fn parse(data: &[u8], endian: Endian) -> Option<Self> {
+        if data.len() < ACID_SIZE {
+            return None;
+        }
+        Some(Self {
+            flags: u32::from_le_bytes(field(data, 0, endian)),
+            root_note: u16::from_le_bytes(field(data, 4, endian)),
+            reserved1: u16::from_le_bytes(field(data, 6, endian)),
+            reserved2: f32::from_le_bytes(field(data, 8, endian)),
+            beats: u32::from_le_bytes(field(data, 12, endian)),
+            meter_denominator: u16::from_le_bytes(field(data, 16, endian)),
+            meter_numerator: u16::from_le_bytes(field(data, 18, endian)),
+            tempo: f32::from_le_bytes(field(data, 20, endian)),
+        })
+    }
+    /// make the chunk payload
+    pub fn make_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = Vec::with_capacity(ACID_SIZE);
+        put_field(&mut block, self.flags.to_le_bytes(), endian);
+        put_field(&mut block, self.root_note.to_le_bytes(), endian);
+        put_field(&mut block, self.reserved1.to_le_bytes(), endian);
+        put_field(&mut block, self.reserved2.to_le_bytes(), endian);
+        put_field(&mut block, self.beats.to_le_bytes(), endian);
+        put_field(&mut block, self.meter_denominator.to_le_bytes(), endian);
+        put_field(&mut block, self.meter_numerator.to_le_bytes(), endian);
+        put_field(&mut block, self.tempo.to_le_bytes(), endian);
+        block
+    }
+}
+
+/// Broadcast cart `cart` chunk
+/// (ref) AES46-2002
+#[derive(Debug,Clone,PartialEq)]
+pub struct CartChunk {
+    pub version: String, // "0101"
+    pub title: String, // 64 bytes
+    pub artist: String, // 64 bytes
+    pub cut_id: String, // 64 bytes
+    pub client_id: String, // 64 bytes
+    pub category: String, // 64 bytes
+    pub classification: String, // 64 bytes
+    pub out_cue: String, // 64 bytes
+    pub start_date: String, // yyyy/mm/dd
+    pub start_time: String, // hh:mm:ss
+    pub end_date: String, // yyyy/mm/dd
+    pub end_time: String, // hh:mm:ss
+    pub producer_app_id: String, // 64 bytes
+    pub producer_app_version: String, // 64 bytes
+    pub user_def: String, // 64 bytes
+    pub level_reference: i32, // sample value of 0 dB reference
+    pub post_timers: Vec<CartTimer>, // up to 8 timers
+    pub url: String, // 1024 bytes
+    pub tag_text: String,
+}
+
+/// Timer of the cart chunk (e.g. "SEGs" = segue start)
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct CartTimer {
+    pub usage: String, // 4 characters
+    pub value: u32, // sample frames from the start
+}
+
+/// size of the cart fields before the tag text
+const CART_FIXED_SIZE: usize = 2048;
+/// number of timers of the cart chunk
+const CART_TIMERS: usize = 8;
+
+impl Default for CartChunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CartChunk {
+    /// empty version 1.01 chunk
+    pub fn new() -> Self {
+        Self {
+            version: "0101".to_string(),
+            title: String::new(),
+            artist: String::new(),
+            cut_id: String::new(),
+            client_id: String::new(),
+            category: String::new(),
+            classification: String::new(),
+            out_cue: String::new(),
+            start_date: String::new(),
+            start_time: String::new(),
+            end_date: String::new(),
+            end_time: String::new(),
+            producer_app_id: String::new(),
+            producer_app_version: String::new(),
+            user_def: String::new(),
+            level_reference: 0,
+            post_timers: vec![],
+            url: String::new(),
+            tag_text: String::new(),
+        }
+    }
+    /// timer value by usage id
+    pub fn timer(&self, usage: &str) -> Option<u32> {
+        self.post_timers.iter().find(|t| t.usage == usage).map(|t| t.value)
+    }
+    /// parse the chunk payload (None if it is too short)
+    /// Unused timers (empty usage) are dropped
+    pub fn parse(data: &[u8], endian: Endian) -> Option<Self> {
+        if data.len() < CART_FIXED_SIZE {
+            return None;
+        }
+        let post_timers = data[684..748].chunks_exact(8)
+            .filter(|t| t[..4].iter().any(|b| *b != 0))
+            .map(|t| CartTimer { usage: text(&t[..4]), value: u32::from_le_bytes(field(t, 4, endian)) })
+            .collect();
+        Some(Self {
+            version: text(&data[0..4]),
+            title: text(&data[4..68]),
+            artist: text(&data[68..132]),
+            cut_id: text(&data[132..196]),
+            client_id: text(&data[196..260]),
+            category: text(&data[260..324]),
+            classification: text(&data[324..388]),
+            out_cue: text(&data[388..452]),
+            start_date: text(&data[452..462]),
+            start_time: text(&data[462..470]),
+            end_date: text(&data[470..480]),
+            end_time: text(&data[480..488]),
+            producer_app_id: text(&data[488..552]),
+            producer_app_version: text(&data[552..616]),
+            user_def: text(&data[616..680]),
+            level_reference: i32::from_le_bytes(field(data, 680, endian)),
+            post_timers,
+            url: text(&data[1024..2048]),
+            tag_text: text(&data[CART_FIXED_SIZE..]),
+        })
+    }
+    /// make the chunk payload (text fields are cut to their size, timers to 8)
+    pub fn make_block(&self, endian: Endian) -> Vec<u8> {
+        let mut block = Vec::with_capacity(CART_FIXED_SIZE + self.tag_text.len());
+        put_text(&mut block, &self.version, 4);
+        for s in [
+            &self.title, &self.artist, &self.cut_id, &self.client_id,
+            &self.category, &self.classification, &self.out_cue,
+        ] {
+            put_text(&mut block, s, 64);
+        }
+        put_text(&mut block, &self.start_date, 10);
+        put_text(&mut block, &self.start_time, 8);
+        put_text(&mut block, &self.end_date, 10);
+        put_text(&mut block, &self.end_time, 8);
+        put_text(&mut block, &self.producer_app_id, 64);
+        put_text(&mut block, &self.producer_app_version, 64);
+        put_text(&mut block, &self.user_def, 64);
+        put_field(&mut block, self.level_reference.to_le_bytes(), endian);
+        for t in self.post_timers.iter().take(CART_TIMERS) {
+            put_text(&mut block, &t.usage, 4);
+            put_field(&mut block, t.value.to_le_bytes(), endian);
+        }
+        block.resize(1024, 0); // unused timers and reserved
+        put_text(&mut block, &self.url, 1024);
+        block.extend_from_slice(self.tag_text.as_bytes());
+        block
+    }
+}
+
+/// ID3v2 tag of the `id3 ` (or `ID3 `) chunk
+/// (ref) https://id3.org/id3v2.3.0 , https://id3.org/id3v2.4.0-structure
+///
+/// The common frames are parsed into the fields, the other frames are written back as they are.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Id3Tag {
+    pub version: u8, // 3 (ID3v2.3) or 4 (ID3v2.4)
+    pub title: Option<String>, // TIT2
+    pub artist: Option<String>, // TPE1
+    pub album: Option<String>, // TALB
+    pub track: Option<String>, // TRCK ("3" or "3/12")
+    pub year: Option<String>, // TYER (v2.3) or TDRC (v2.4)
+    pub comment: Option<String>, // COMM
+    pub picture: Option<Id3Picture>, // APIC
+    pub other_frames: Vec<Id3Frame>,
+}
+
+/// Embedded picture (APIC frame)
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Id3Picture {
+    pub mime: String, // "image/jpeg" etc
+    pub picture_type: u8, // 3 = front cover
+    pub description: String,
+    pub data: Vec<u8>,
+}
+
+/// Frame kept as it is
+#[derive(Debug,Clone,PartialEq)]
+pub struct Id3Frame {
+    pub id: String,
+    pub flags: u16,
+    pub data: Vec<u8>,
+}
+
+/// ID3 frames and the LIST INFO ids they are merged with (in the order of the fields)
+const ID3_INFO_IDS: [(&str, &str); 6] = [
+    ("TIT2", "INAM"),
+    ("TPE1", "IART"),
+    ("TALB", "IPRD"),
+    ("TRCK", "ITRK"),
+    ("YEAR", "ICRD"),
+    ("COMM", "ICMT"),
+];
+
+impl Default for Id3Tag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Id3Tag {
+    /// empty ID3v2.3 tag
+    pub fn new() -> Self {
+        Self {
+            version: 3,
+            title: None,
+            artist: None,
+            album: None,
+            track: None,
+            year: None,
+            comment: None,
+            picture: None,
+            other_frames: vec![],
+        }
+    }
+    /// tag with the LIST INFO items that have an ID3 frame
+    pub fn from_list_items(items: &[ListChunkItem]) -> Self {
+        let mut tag = Self::new();
+        for it in items.iter() {
+            if let Some((frame, _)) = ID3_INFO_IDS.iter().find(|(_, info)| *info == it.id) {
+                *tag.text_field(frame) = Some(it.value.clone());
+            }
+        }
+        tag
+    }
+    /// text fields as LIST INFO items
+    pub fn to_list_items(&self) -> Vec<ListChunkItem> {
+        let values = [&self.title, &self.artist, &self.album, &self.track, &self.year, &self.comment];
+        ID3_INFO_IDS.iter().zip(values).filter_map(|((_, info), value)| {
+            value.clone().map(|value| ListChunkItem { id: info.to_string(), value })
+        }).collect()
+    }
+    fn text_field(&mut self, frame: &str) -> &mut Option<String> {
+        match frame {
+            "TIT2" => &mut self.title,
+            "TPE1" => &mut self.artist,
+            "TALB" => &mut self.album,
+            "TRCK" => &mut self.track,
+            "COMM" => &mut self.comment,
+            _ => &mut self.year,
+        }
+    }
+    /// parse the ID3v2.3 / ID3v2.4 tag (None for other versions)
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 10 || &data[0..3] != b"ID3" || !(3..=4).contains(&data[3]) {
+            return None;
+        }
+        let version = data[3];
+        let tag_flags = data[5];
+        let size = syncsafe(&data[6..10]) as usize;
+        let mut body = data.get(10..10 + size).unwrap_or(&data[10..]).to_vec();
+        if version == 3 && tag_flags & 0x80 != 0 {
+            body = resync(&body);
+        }
+        let mut pos = 0;
+        // extended header
+        if tag_flags & 0x40 != 0 && body.len() >= 4 {
+            pos = match version {
+                3 => 4 + u32::from_le_bytes(field(&body, 0, Endian::Big)) as usize,
+                _ => syncsafe(&body[0..4]) as usize,
+            };
+        }
+        let mut tag = Self { version, ..Self::new() };
+        while pos + 10 <= body.len() && body[pos] != 0 {
+            let id = String::from_utf8_lossy(&body[pos..pos + 4]).to_string();
+            let size = match version {
+                3 => u32::from_le_bytes(field(&body, pos + 4, Endian::Big)) as usize,
+                _ => syncsafe(&body[pos + 4..pos + 8]) as usize,
+            };
+            let flags = u16::from_be_bytes([body[pos + 8], body[pos + 9]]);
+            let Some(data) = body.get(pos + 10..pos + 10 + size) else { break };
+            pos += 10 + size;
+            if !tag.read_frame(&id, flags, data, tag_flags & 0x80 != 0) {
+                // the tag is written without unsynchronisation, v2.4 marks it on the frame instead
+                let flags = if version == 4 && tag_flags & 0x80 != 0 { flags | 0x0002 } else { flags };
+                tag.other_frames.push(Id3Frame { id, flags, data: data.to_vec() });
+            }
+        }
+        Some(tag)
+    }
+    /// set the field of a common frame (false if the frame is kept as it is)
+    fn read_frame(&mut self, id: &str, flags: u16, data: &[u8], unsync: bool) -> bool {
+        // compressed or encrypted frames are not decoded
+        let (packed, grouped) = match self.version {
+            3 => (flags & 0x00C0 != 0, flags & 0x0020 != 0),
+            _ => (flags & 0x000C != 0, flags & 0x0040 != 0),
+        };
+        if packed {
+            return false;
+        }
+        let mut data = if grouped { data.get(1..).unwrap_or(&[]) } else { data };
+        if self.version == 4 && flags & 0x0001 != 0 {
+            data = data.get(4..).unwrap_or(&[]);
+        }
+        let data = if self.version == 4 && (unsync || flags & 0x0002 != 0) { resync(data) } else { data.to_vec() };
+        let Some((&encoding, rest)) = data.split_first() else { return false };
+        let year = if self.version == 3 { "TYER" } else { "TDRC" };
+        match id {
+            "TIT2" | "TPE1" | "TALB" | "TRCK" if self.text_field(id).is_none() => {
+                *self.text_field(id) = Some(id3_text(encoding, rest));
+            },
+            _ if id == year && self.year.is_none() => {
+                self.year = Some(id3_text(encoding, rest));
+            },
+            // language(3), short description, text
+            "COMM" if self.comment.is_none() && rest.len() >= 3 => {
+                let (_, text) = id3_split(encoding, &rest[3..]);
+                self.comment = Some(id3_text(encoding, text));
+            },
+            // mime type, picture type, description, picture data
+            "APIC" if self.picture.is_none() => {
+                let (mime, rest) = id3_split(0, rest);
+                let Some((&picture_type, rest)) = rest.split_first() else { return false };
+                let (description, data) = id3_split(encoding, rest);
+                self.picture = Some(Id3Picture {
+                    mime: id3_text(0, mime),
+                    picture_type,
+                    description: id3_text(encoding, description),
+                    data: data.to_vec(),
+                });
+            },
+            _ => return false,
+        }
+        true
+    }
+    /// make the ID3v2 tag (without padding and unsynchronisation)
+    pub fn make_block(&self) -> Vec<u8> {
+        let version = if self.version == 4 { 4 } else { 3 };
+        let mut frames: Vec<(String, u16, Vec<u8>)> = vec![];
+        let year = if version == 3 { "TYER" } else { "TDRC" };
+        for (id, value) in [
+            ("TIT2", &self.title),
+            ("TPE1", &self.artist),
+            ("TALB", &self.album),
+            ("TRCK", &self.track),
+            (year, &self.year),
+        ] {
+            if let Some(value) = value {
+                let encoding = id3_encoding(version, value);
+                let mut data = vec![encoding];
+                put_id3_text(&mut data, encoding, value, false);
+                frames.push((id.to_string(), 0, data));
+            }
+        }
+        if let Some(comment) = &self.comment {
+            let encoding = id3_encoding(version, comment);
+            let mut data = vec![encoding];
+            data.extend_from_slice(b"eng");
+            put_id3_text(&mut data, encoding, "", true);
+            put_id3_text(&mut data, encoding, comment, false);
+            frames.push(("COMM".to_string(), 0, data));
+        }
+        if let Some(pic) = &self.picture {
+            let encoding = id3_encoding(version, &pic.description);
+            let mut data = vec![encoding];
+            put_id3_text(&mut data, 0, &pic.mime, true);
+            data.push(pic.picture_type);
+            put_id3_text(&mut data, encoding, &pic.description, true);
+            data.extend_from_slice(&pic.data);
+            frames.push(("APIC".to_string(), 0, data));
+        }
+        for f in self.other_frames.iter() {
+            frames.push((f.id.clone(), f.flags, f.data.clone()));
+        }
+        let mut body = vec![];
+        for (id, flags, data) in frames.iter() {
+            put_text(&mut body, id, 4);
+            let size = data.len() as u32;
+            body.extend_from_slice(&if version == 3 { size.to_be_bytes() } else { to_syncsafe(size) });
+            body.extend_from_slice(&flags.to_be_bytes());
+            body.extend_from_slice(data);
+        }
+        let mut block = b"ID3".to_vec();
+        block.extend_from_slice(&[version, 0, 0]);
+        block.extend_from_slice(&to_syncsafe(body.len() as u32));
+        block.extend_from_slice(&body);
+        block
+    }
+}
+
+/// 28-bit integer stored in 7 bits per byte
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |v, b| (v << 7) | (*b & 0x7F) as u32)
+}
+
+fn to_syncsafe(v: u32) -> [u8; 4] {
+    [(v >> 21) as u8 & 0x7F, (v >> 14) as u8 & 0x7F, (v >> 7) as u8 & 0x7F, v as u8 & 0x7F]
+}
+
+/// remove the zero bytes inserted after 0xFF by the unsynchronisation
+fn resync(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, b) in data.iter().enumerate() {
+        if *b == 0 && i > 0 && data[i - 1] == 0xFF {
+            continue;
+        }
+        out.push(*b);
+    }
+    out
+}
+
+/// ISO-8859-1 when possible in ID3v2.3, UTF-16 otherwise, always UTF-8 in ID3v2.4
+fn id3_encoding(version: u8, s: &str) -> u8 {
+    if version == 4 {
+        3
+    } else if s.chars().all(|c| (c as u32) < 0x100) {
+        0
+    } else {
+        1
+    }
+}
+
+/// text of an ID3 frame up to the terminator
+fn id3_text(encoding: u8, bytes: &[u8]) -> String {
+    let (bytes, _) = id3_split(encoding, bytes);
+    match encoding {
+        0 => bytes.iter().map(|b| *b as char).collect(),
+        1 | 2 => {
+            let (big, bytes) = match bytes {
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                _ => (encoding == 2, bytes),
+            };
+            let units: Vec<u16> = bytes.chunks_exact(2)
+                .map(|c| if big { u16::from_be_bytes([c[0], c[1]]) } else { u16::from_le_bytes([c[0], c[1]]) })
+                .collect();
+            String::from_utf16_lossy(&units)
+        },
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// split at the terminator of the encoding (two zero bytes for UTF-16)
+fn id3_split(encoding: u8, bytes: &[u8]) -> (&[u8], &[u8]) {
+    let wide = matches!(encoding, 1 | 2);
+    let end = if wide {
+        bytes.chunks_exact(2).position(|c| c == [0, 0]).map(|i| i * 2)
+    } else {
+        bytes.iter().position(|b| *b == 0)
+    };
+    match end {
+        Some(end) => (&bytes[..end], &bytes[end + if wide { 2 } else { 1 }..]),
+        None => (bytes, &[]),
+    }
+}
+
+fn put_id3_text(data: &mut Vec<u8>, encoding: u8, s: &str, terminated: bool) {
+    match encoding {
+        0 => data.extend(s.chars().map(|c| c as u8)),
+        1 => {
+            data.extend_from_slice(&[0xFF, 0xFE]);
+            data.extend(s.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        },
+        _ => data.extend_from_slice(s.as_bytes()),
+    }
+    if terminated {
+        data.extend_from_slice(if encoding == 1 { &[0, 0] } else { &[0] });
+    }
+}
+
+/// Standard LIST INFO field
+/// (ref) https://www.recordingblogs.com/wiki/list-chunk-of-a-wave-file
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum InfoField {
+    ArchivalLocation, // IARL
+    Artist, // IART
+    Commissioned, // ICMS
+    Comment, // ICMT
+    Copyright, // ICOP
+    CreationDate, // ICRD (yyyy-mm-dd)
+    Cropped, // ICRP
+    Dimensions, // IDIM
+    DotsPerInch, // IDPI
+    Engineer, // IENG
+    Genre, // IGNR
+    Keywords, // IKEY
+    Lightness, // ILGT
+    Medium, // IMED
+    Title, // INAM
+    Palette, // IPLT
+    Product, // IPRD (album)
+    Subject, // ISBJ
+    Software, // ISFT
+    Sharpness, // ISHP
+    Source, // ISRC
+    SourceForm, // ISRF
+    Technician, // ITCH
+    TableOfContents, // ITOC (CD table of contents)
+    Track, // ITRK
+}
+
+impl InfoField {
+    /// all fields in the order of their ids
+    pub const ALL: [InfoField; 25] = [
+        InfoField::ArchivalLocation, InfoField::Artist, InfoField::Commissioned, InfoField::Comment,
+        InfoField::Copyright, InfoField::CreationDate, InfoField::Cropped, InfoField::Dimensions,
+        InfoField::DotsPerInch, InfoField::Engineer, InfoField::Genre, InfoField::Keywords,
+        InfoField::Lightness, InfoField::Medium, InfoField::Title, InfoField::Palette,
+        InfoField::Product, InfoField::Subject, InfoField::Software, InfoField::Sharpness,
+        InfoField::Source, InfoField::SourceForm, InfoField::Technician, InfoField::TableOfContents,
+        InfoField::Track,
+    ];
+    /// four character id of the INFO item
+    pub fn id(&self) -> &'static str {
+        match self {
+            InfoField::ArchivalLocation => "IARL",
+            InfoField::Artist => "IART",
+            InfoField::Commissioned => "ICMS",
+            InfoField::Comment => "ICMT",
+            InfoField::Copyright => "ICOP",
+            InfoField::CreationDate => "ICRD",
+            InfoField::Cropped => "ICRP",
+            InfoField::Dimensions => "IDIM",
+            InfoField::DotsPerInch => "IDPI",
+            InfoField::Engineer => "IENG",
+            InfoField::Genre => "IGNR",
+            InfoField::Keywords => "IKEY",
+            InfoField::Lightness => "ILGT",
+            InfoField::Medium => "IMED",
+            InfoField::Title => "INAM",
+            InfoField::Palette => "IPLT",
+            InfoField::Product => "IPRD",
+            InfoField::Subject => "ISBJ",
+            InfoField::Software => "ISFT",
+            InfoField::Sharpness => "ISHP",
+            InfoField::Source => "ISRC",
+            InfoField::SourceForm => "ISRF",
+            InfoField::Technician => "ITCH",
+            InfoField::TableOfContents => "ITOC",
+            InfoField::Track => "ITRK",
+        }
+    }
+    /// field of a standard id
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.id() == id)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MetadataError {
+    #[error("Invalid INFO id {id:?}, it must be four ASCII letters, digits or spaces starting with a letter")]
+    InvalidInfoId {
+        id: String,
+    },
+}
+
+/// the id can be written as a LIST INFO item
+pub fn is_valid_info_id(id: &str) -> bool {
+    let bytes = id.as_bytes();
+    bytes.len() == 4
+        && bytes[0].is_ascii_alphabetic()
+        && bytes.iter().all(|b| b.is_ascii_alphanumeric() || *b == b' ')
+}
+
+/// Typed view of the LIST INFO items
+///
+/// The standard fields are set with `InfoField`, other valid ids are kept as custom items.
+/// The items keep their order when converted back to a `ListChunk`.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct WavMetadata {
+    items: Vec<ListChunkItem>,
+}
+
+impl WavMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// view of the LIST chunk (items with invalid ids are dropped, the first of duplicated ids is used)
+    pub fn from_list_chunk(list: &ListChunk) -> Self {
+        Self::from_items(&list.items)
+    }
+    pub fn from_items(items: &[ListChunkItem]) -> Self {
+        let mut meta = Self::new();
+        for it in items.iter() {
+            if is_valid_info_id(&it.id) && meta.get_custom(&it.id).is_none() {
+                meta.items.push(it.clone());
+            }
+        }
+        meta
+    }
+    pub fn to_list_chunk(&self) -> ListChunk {
+        ListChunk { items: self.items.clone() }
+    }
+    /// all items in order
+    pub fn items(&self) -> &[ListChunkItem] {
+        &self.items
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+    pub fn get(&self, field: InfoField) -> Option<&str> {
+        self.get_custom(field.id())
+    }
+    pub fn set(&mut self, field: InfoField, value: &str) {
+        self.put(field.id(), value);
+    }
+    pub fn remove(&mut self, field: InfoField) -> Option<String> {
+        let pos = self.items.iter().position(|it| it.id == field.id())?;
+        Some(self.items.remove(pos).value)
+    }
+    /// value of any id (standard or custom)
+    pub fn get_custom(&self, id: &str) -> Option<&str> {
+        self.items.iter().find(|it| it.id == id).map(|it| it.value.as_str())
+    }
+    /// set the value of a non-standard id (e.g. "IENC")
+    pub fn set_custom(&mut self, id: &str, value: &str) -> Result<(), MetadataError> {
+        if !is_valid_info_id(id) {
+            return Err(MetadataError::InvalidInfoId { id: id.to_string() });
+        }
+        self.put(id, value);
+        Ok(())
+    }
+    fn put(&mut self, id: &str, value: &str) {
+        match self.items.iter_mut().find(|it| it.id == id) {
+            Some(it) => it.value = value.to_string(),
+            None => self.items.push(ListChunkItem { id: id.to_string(), value: value.to_string() }),
+        }
+    }
+    /// INAM
+    pub fn title(&self) -> Option<&str> { self.get(InfoField::Title) }
+    pub fn set_title(&mut self, value: &str) { self.set(InfoField::Title, value) }
+    /// IART
+    pub fn artist(&self) -> Option<&str> { self.get(InfoField::Artist) }
+    pub fn set_artist(&mut self, value: &str) { self.set(InfoField::Artist, value) }
+    /// IPRD
+    pub fn album(&self) -> Option<&str> { self.get(InfoField::Product) }
+    pub fn set_album(&mut self, value: &str) { self.set(InfoField::Product, value) }
+    /// IGNR
+    pub fn genre(&self) -> Option<&str> { self.get(InfoField::Genre) }
+    pub fn set_genre(&mut self, value: &str) { self.set(InfoField::Genre, value) }
+    /// ICMT
+    pub fn comment(&self) -> Option<&str> { self.get(InfoField::Comment) }
+    pub fn set_comment(&mut self, value: &str) { self.set(InfoField::Comment, value) }
+    /// ICOP
+    pub fn copyright(&self) -> Option<&str> { self.get(InfoField::Copyright) }
+    pub fn set_copyright(&mut self, value: &str) { self.set(InfoField::Copyright, value) }
+    /// ICRD
+    pub fn creation_date(&self) -> Option<&str> { self.get(InfoField::CreationDate) }
+    pub fn set_creation_date(&mut self, value: &str) { self.set(InfoField::CreationDate, value) }
+    /// ISFT
+    pub fn software(&self) -> Option<&str> { self.get(InfoField::Software) }
+    pub fn set_software(&mut self, value: &str) { self.set(InfoField::Software, value) }
+    /// ITRK (a number or "3/12")
+    pub fn track(&self) -> Option<u32> {
+        self.get(InfoField::Track)?.split('/').next()?.trim().parse().ok()
+    }
+    pub fn set_track(&mut self, track: u32) { self.set(InfoField::Track, &track.to_string()) }
+}
+
+impl From<&ListChunk> for WavMetadata {
+    fn from(list: &ListChunk) -> Self {
+        Self::from_list_chunk(list)
+    }
+}
+
+impl From<&WavMetadata> for ListChunk {
+    fn from(meta: &WavMetadata) -> Self {
+        meta.to_list_chunk()
+    }
+}
+
+/// text between the first `<tag>` and the following `</tag>`
+fn xml_content<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    xml_element(xml, tag).map(|(content, _)| content)
+}
+
+/// text of the first `tag` element and the position after its end tag
+fn xml_element<'a>(xml: &'a str, tag: &str) -> Option<(&'a str, usize)> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some((&xml[start..end], end + close.len()))
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// little-endian copy of the field at `pos`
+fn field<const N: usize>(data: &[u8], pos: usize, endian: Endian) -> [u8; N] {
+    let mut bytes = [0; N];
+    bytes.copy_from_slice(&data[pos..pos + N]);
+    if endian == Endian::Big {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// append a field given in little-endian order
+fn put_field<const N: usize>(block: &mut Vec<u8>, mut bytes: [u8; N], endian: Endian) {
+    if endian == Endian::Big {
+        bytes.reverse();
+    }
+    block.extend_from_slice(&bytes);
+}
+
+/// text up to the first null byte
+fn text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+/// text padded with null bytes to `size`
+fn put_text(block: &mut Vec<u8>, s: &str, size: usize) {
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(size);
+    block.extend_from_slice(&bytes[..len]);
+    block.resize(block.len() + size - len, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn bext_versions() {
+        let mut bext = BextChunk::new();
+        bext.description = "Evening news".to_string();
+        bext.originator = "Studio 4".to_string();
+        bext.origination_date = "2024:05:01".to_string();
+        bext.origination_time = "18:30:00".to_string();
+        bext.time_reference = 48_000 * 3600 * 18 + (1 << 32);
+        bext.umid[0] = 0x06;
+        bext.loudness_value = -2300;
+        bext.max_true_peak_level = -100;
+        bext.coding_history = "A=PCM,F=48000,W=24,M=stereo\r\n".to_string();
+        let block = bext.make_block(Endian::Little);
+        assert_eq!(block.len(), 602 + 29);
+        assert_eq!(&block[320..330], b"2024:05:01");
+        assert_eq!(u16::from_le_bytes([block[346], block[347]]), 2);
+        assert_eq!(BextChunk::parse(&block, Endian::Little), Some(bext.clone()));
+        assert_eq!(BextChunk::parse(&bext.make_block(Endian::Big), Endian::Big), Some(bext.clone()));
+
+        // version 1 has no loudness fields, version 0 no UMID
+        bext.version = 1;
+        let v1 = BextChunk::parse(&bext.make_block(Endian::Little), Endian::Little).unwrap();
+        assert_eq!(v1.umid[0], 0x06);
+        assert_eq!(v1.loudness_value, 0);
+        bext.version = 0;
+        let v0 = BextChunk::parse(&bext.make_block(Endian::Little), Endian::Little).unwrap();
+        assert_eq!(v0.umid, [0; 64]);
+        assert_eq!(v0.description, "Evening news");
+
+        assert_eq!(BextChunk::parse(&[0; 601], Endian::Little), None);
+    }
+
+    #[test]
+    fn bext_in_wav() {
+        let mut head = crate::header::WavHeader::new_mono();
+        let mut bext = BextChunk::new();
+        bext.originator = "field recorder".to_string();
+        bext.coding_history = "A=PCM,F=44100,W=32,M=mono".to_string(); // odd size
+        head.bext = Some(bext);
+        let bytes = crate::writer::to_bytes(&head, &[0.5, -0.5]).unwrap();
+        assert_eq!(&bytes[36..40], b"bext");
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 602 + 25);
+        let (wav, _) = crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(wav.header.bext, head.bext);
+        assert!(wav.header.unknown_chunks.is_empty());
+        assert_eq!(wav.samples, vec![0.5, -0.5]);
+    }
+
+    #[test]
+    fn ixml_fields() {
+        let xml = "<?xml version=\"1.0\"?><BWFXML><PROJECT>Feature &amp; Co</PROJECT><SCENE>12A</SCENE><TAKE>3</TAKE>\
+            <TRACK_LIST><TRACK_COUNT>2</TRACK_COUNT>\
+            <TRACK><CHANNEL_INDEX>1</CHANNEL_INDEX><INTERLEAVE_INDEX>1</INTERLEAVE_INDEX><NAME>Boom</NAME></TRACK>\
+            <TRACK><CHANNEL_INDEX>2</CHANNEL_INDEX><INTERLEAVE_INDEX>2</INTERLEAVE_INDEX><NAME>Lav 1</NAME></TRACK>\
+            </TRACK_LIST></BWFXML>";
+        let mut data = xml.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0]);
+        let ixml = IxmlChunk::parse(&data);
+        assert_eq!(ixml.xml, xml);
+        let fields = ixml.fields();
+        assert_eq!(fields.project.as_deref(), Some("Feature & Co"));
+        assert_eq!(fields.scene.as_deref(), Some("12A"));
+        assert_eq!(fields.take.as_deref(), Some("3"));
+        assert_eq!(fields.tape, None);
+        assert_eq!(fields.tracks.len(), 2);
+        assert_eq!(fields.tracks[1].name, "Lav 1");
+        assert_eq!(fields.tracks[1].interleave_index, 2);
+        assert_eq!(IxmlChunk::from_fields(&fields).fields(), fields);
+
+        // written and read back with the WAV file
+        let mut head = crate::header::WavHeader::new_stereo();
+        head.ixml = Some(IxmlChunk::from_fields(&fields));
+        let bytes = crate::writer::to_bytes(&head, &[0.0; 4]).unwrap();
+        let (wav, _) = crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(wav.header.ixml, head.ixml);
+    }
+
+    /// headers of `head` written to and read back from WAV and RIFX files (every chunk is parsed)
+    fn riff_and_rifx_roundtrip(head: &crate::header::WavHeader) -> Vec<crate::header::WavHeader> {
+        [crate::header::ContainerKind::Riff, crate::header::ContainerKind::Rifx].into_iter().map(|kind| {
+            let bytes = crate::writer::to_bytes_as(head, &[0.0; 8], kind).unwrap();
+            let (wav, _) = crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap();
+            assert!(wav.header.unknown_chunks.is_empty());
+            wav.header
+        }).collect()
+    }
+
+    #[test]
+    fn cue_markers_and_regions() {
+        let mut cue = CueChunk::default();
+        assert_eq!(cue.add_marker(0, "intro"), 1);
+        assert_eq!(cue.add_marker(4, ""), 2);
+        let id = cue.add_marker(6, "chorus");
+        cue.adtl.push(AdtlItem::Note { cue_id: 2, text: "breath".to_string() });
+        assert_eq!(cue.label(1), Some("intro"));
+        assert_eq!(cue.label(2), None);
+
+        let points = cue.make_points_block(Endian::Little);
+        assert_eq!(points.len(), 4 + 3 * 24);
+        assert_eq!(&points[12..16], b"data");
+        assert_eq!(CueChunk::parse_points(&points, Endian::Little).unwrap(), cue.points);
+        assert_eq!(CueChunk::parse_points(&points[..50], Endian::Little), None);
+        // "intro\0" fits, "breath\0" and "chorus\0" are padded to an even size
+        let adtl = cue.make_adtl_block(Endian::Big);
+        assert_eq!(adtl.len(), 4 + (8 + 10) + (8 + 12) + (8 + 12));
+        assert_eq!(CueChunk::parse_adtl(&adtl, Endian::Big).unwrap(), cue.adtl);
+
+        // markers split the samples, regions are used as they are
+        let frames = 8;
+        let ranges = crate::splitter::split_by_cues(&cue, frames);
+        assert_eq!(ranges.iter().map(|r| (r.start, r.end)).collect::<Vec<_>>(), vec![(0, 4), (4, 6), (6, 8)]);
+        cue.adtl.push(AdtlItem::LabeledText {
+            cue_id: id, sample_length: 10, purpose: "rgn ".to_string(),
+            country: 0, language: 0, dialect: 0, code_page: 0, text: "chorus".to_string(),
+        });
+        let ranges = crate::splitter::split_by_cues(&cue, frames);
+        assert_eq!(ranges.iter().map(|r| (r.start, r.end)).collect::<Vec<_>>(), vec![(6, 8)]);
+
+        // written and read back with the WAV and RIFX files
+        let mut head = crate::header::WavHeader::new_mono();
+        head.cue = Some(cue);
+        for h in riff_and_rifx_roundtrip(&head) {
+            assert_eq!(h.cue, head.cue);
+        }
+
+        // items that are not modelled (`file`, a broken `ltxt`) are written back as they are
+        let mut adtl = b"adtl".to_vec();
+        adtl.extend_from_slice(b"labl\x0a\0\0\0\x01\0\0\0intro\0");
+        adtl.extend_from_slice(b"file\x09\0\0\0\x01\0\0\0TEXTx\0");
+        adtl.extend_from_slice(b"ltxt\x08\0\0\0\x01\0\0\0\x10\0\0\0");
+        let items = CueChunk::parse_adtl(&adtl, Endian::Little).unwrap();
+        assert_eq!(items[1], AdtlItem::Other { id: "file".to_string(), data: b"\x01\0\0\0TEXTx".to_vec() });
+        assert_eq!(items[2].cue_id(), None);
+        let cue = CueChunk { points: vec![], adtl: items };
+        assert_eq!(cue.make_adtl_block(Endian::Little), adtl);
+        head.cue = Some(cue);
+        for h in riff_and_rifx_roundtrip(&head) {
+            assert_eq!(h.cue, head.cue);
+        }
+        // a list of such items only is kept as an unknown chunk
+        assert_eq!(CueChunk::parse_adtl(&adtl[..4].iter().chain(&adtl[22..]).copied().collect::<Vec<_>>(), Endian::Little), None);
+    }
+
+    #[test]
+    fn smpl_and_inst() {
+        let mut smpl = SmplChunk::new(44_100);
+        assert_eq!(smpl.sample_period, 22_675);
+        smpl.midi_unity_note = 57;
+        smpl.midi_pitch_fraction = 0x4000_0000;
+        smpl.smpte_format = 25;
+        smpl.smpte_offset = 0x01_02_03_04;
+        smpl.loops.push(SampleLoop { start: 100, end: 999, ..SampleLoop::default() });
+        smpl.sampler_data = vec![1, 2, 3];
+        assert_eq!(smpl.pitch_cents(), 25.0);
+        let block = smpl.make_block(Endian::Little);
+        assert_eq!(block.len(), 36 + 24 + 3);
+        assert_eq!(&block[28..36], &[1, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(SmplChunk::parse(&block, Endian::Little), Some(smpl.clone()));
+        assert_eq!(SmplChunk::parse(&block[..50], Endian::Little), None);
+
+        let inst = InstChunk { fine_tune: -12, gain: -3, low_note: 48, high_note: 64, ..InstChunk::new() };
+        assert_eq!(inst.make_block(), vec![60, 244, 253, 48, 64, 1, 127]);
+        assert_eq!(InstChunk::parse(&inst.make_block()), Some(inst.clone()));
+        assert_eq!(InstChunk::parse(&[60, 0]), None);
+
+        // written and read back with the WAV and RIFX files
+        let mut head = crate::header::WavHeader::new_mono();
+        head.smpl = Some(smpl);
+        head.inst = Some(inst);
+        for h in riff_and_rifx_roundtrip(&head) {
+            assert_eq!(h.smpl, head.smpl);
+            assert_eq!(h.inst, head.inst);
+        }
+    }
+
+    #[test]
+    fn acid_and_cart() {
+        let mut acid = AcidChunk::new();
+        acid.set_one_shot(true);
+        acid.flags |= ACID_ROOT_NOTE;
+        acid.root_note = 57;
+        acid.beats = 8;
+        acid.tempo = 98.5;
+        assert!(acid.is_one_shot());
+        assert_eq!(acid.root(), Some(57));
+        let block = acid.make_block(Endian::Little);
+        assert_eq!(block.len(), 24);
+        assert_eq!(&block[20..24], &98.5f32.to_le_bytes());
+        assert_eq!(AcidChunk::parse(&block, Endian::Little), Some(acid.clone()));
+        acid.set_one_shot(false);
+        assert!(!acid.is_one_shot());
+
+        let mut cart = CartChunk::new();
+        cart.title = "Spring sale".to_string();
+        cart.artist = "Shop".to_string();
+        cart.cut_id = "C1234".to_string();
+        cart.start_date = "2024/03/01".to_string();
+        cart.start_time = "00:00:00".to_string();
+        cart.end_date = "2024/03/31".to_string();
+        cart.end_time = "23:59:59".to_string();
+        cart.level_reference = 32768;
+        cart.post_timers = vec![
+            CartTimer { usage: "INTs".to_string(), value: 0 },
+            CartTimer { usage: "SEGs".to_string(), value: 44_100 * 28 },
+        ];
+        cart.url = "http://example.com/spot".to_string();
+        cart.tag_text = "<cart/>".to_string();
+        assert_eq!(cart.timer("SEGs"), Some(44_100 * 28));
+        let block = cart.make_block(Endian::Little);
+        assert_eq!(block.len(), 2048 + 7);
+        assert_eq!(&block[684..688], b"INTs");
+        assert_eq!(&block[1024..1030], b"http:/");
+        assert_eq!(CartChunk::parse(&block, Endian::Little), Some(cart.clone()));
+        assert_eq!(CartChunk::parse(&block[..2000], Endian::Little), None);
+
+        // written and read back with the WAV and RIFX files
+        let mut head = crate::header::WavHeader::new_mono();
+        head.acid = Some(acid);
+        head.cart = Some(cart);
+        for h in riff_and_rifx_roundtrip(&head) {
+            assert_eq!(h.acid, head.acid);
+            assert_eq!(h.cart, head.cart);
+        }
+    }
+
+    #[test]
+    fn id3_frames() {
+        // ID3v2.4 tag with UTF-8, UTF-16 and unsynchronised frames
+        let mut frames = vec![];
+        let mut add = |id: &[u8], flags: u16, data: &[u8]| {
+            frames.extend_from_slice(id);
+            frames.extend_from_slice(&to_syncsafe(data.len() as u32));
+            frames.extend_from_slice(&flags.to_be_bytes());
+            frames.extend_from_slice(data);
+        };
+        add(b"TIT2", 0, b"\x03Caf\xC3\xA9");
+        add(b"TPE1", 0, &[1, 0xFF, 0xFE, b'A', 0, b'B', 0]);
+        add(b"TDRC", 0, b"\x002024");
+        add(b"COMM", 0, b"\x03engdesc\0hello");
+        add(b"APIC", 0x0002, b"\x00image/png\0\x03\0\xFF\x00\xD8");
+        add(b"TXXX", 0, b"\x00key\0value");
+        let mut data = b"ID3\x04\0\0".to_vec();
+        data.extend_from_slice(&to_syncsafe(frames.len() as u32 + 10));
+        data.extend_from_slice(&frames);
+        data.extend_from_slice(&[0; 10]); // padding
+        let tag = Id3Tag::parse(&data).unwrap();
+        assert_eq!(tag.title.as_deref(), Some("Café"));
+        assert_eq!(tag.artist.as_deref(), Some("AB"));
+        assert_eq!(tag.year.as_deref(), Some("2024"));
+        assert_eq!(tag.comment.as_deref(), Some("hello"));
+        let pic = tag.picture.as_ref().unwrap();
+        assert_eq!((pic.mime.as_str(), pic.picture_type), ("image/png", 3));
+        assert_eq!(pic.data, vec![0xFF, 0xD8]);
+        assert_eq!(tag.other_frames.len(), 1);
+        assert_eq!(Id3Tag::parse(&tag.make_block()), Some(tag.clone()));
+        assert_eq!(Id3Tag::parse(b"ID3\x02\0\0\0\0\0\0"), None);
+
+        // the frames of an unsynchronised v2.4 tag that are kept as they are stay unsynchronised
+        let mut frames = b"TIT2\0\0\0\x03\0\0\x00\xFF\x00".to_vec();
+        frames.extend_from_slice(b"PRIV\0\0\0\x05\0\0k\0\xFF\x00\xE0");
+        let mut data = b"ID3\x04\0\x80".to_vec();
+        data.extend_from_slice(&to_syncsafe(frames.len() as u32));
+        data.extend_from_slice(&frames);
+        let tag = Id3Tag::parse(&data).unwrap();
+        assert_eq!(tag.title.as_deref(), Some("\u{FF}"));
+        assert_eq!(tag.other_frames[0].flags, 0x0002);
+        let block = tag.make_block();
+        assert_eq!(block[5], 0);
+        let again = Id3Tag::parse(&block).unwrap();
+        assert_eq!(again, tag);
+        assert_eq!(resync(&again.other_frames[0].data), b"k\0\xFF\xE0");
+
+        // ID3v2.3 uses UTF-16 for the text out of ISO-8859-1
+        let mut v3 = Id3Tag::from_list_items(&[
+            ListChunkItem { id: "INAM".to_string(), value: "東京".to_string() },
+            ListChunkItem { id: "ICRD".to_string(), value: "1999".to_string() },
+            ListChunkItem { id: "ISFT".to_string(), value: "wav_io".to_string() },
+        ]);
+        v3.album = Some("Night".to_string());
+        let block = v3.make_block();
+        assert_eq!(&block[10..14], b"TIT2");
+        assert_eq!(block[20], 1);
+        assert_eq!(Id3Tag::parse(&block), Some(v3.clone()));
+        assert_eq!(v3.to_list_items().iter().map(|it| it.id.as_str()).collect::<Vec<_>>(), vec!["INAM", "IPRD", "ICRD"]);
+
+        // written and read back with the WAV file, LIST INFO items come first
+        let mut head = crate::header::WavHeader::new_mono();
+        head.list_chunk = Some(crate::header::ListChunk {
+            items: vec![ListChunkItem { id: "INAM".to_string(), value: "Tokyo".to_string() }],
+        });
+        head.id3 = Some(v3);
+        let bytes = crate::writer::to_bytes(&head, &[0.0; 8]).unwrap();
+        let (wav, _) = crate::reader::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(wav.header.id3, head.id3);
+        let items = wav.header.info_items();
+        assert_eq!(items.iter().map(|it| it.value.as_str()).collect::<Vec<_>>(), vec!["Tokyo", "Night", "1999"]);
+    }
+
+    #[test]
+    fn info_metadata() {
+        assert_eq!(InfoField::from_id("IPRD"), Some(InfoField::Product));
+        assert_eq!(InfoField::from_id("XXXX"), None);
+        assert!(InfoField::ALL.iter().all(|f| is_valid_info_id(f.id())));
+        assert!(InfoField::ALL.windows(2).all(|w| w[0].id() < w[1].id()));
+        assert!(!is_valid_info_id("IAR"));
+        assert!(!is_valid_info_id("1ART"));
+        assert!(!is_valid_info_id("IA\0T"));
+
+        let list = ListChunk {
+            items: vec![
+                ListChunkItem { id: "INAM".to_string(), value: "Song".to_string() },
+                ListChunkItem { id: "ITRK".to_string(), value: "3/12".to_string() },
+                ListChunkItem { id: "bad!".to_string(), value: "x".to_string() },
+                ListChunkItem { id: "INAM".to_string(), value: "Again".to_string() },
+                ListChunkItem { id: "IENC".to_string(), value: "v1".to_string() },
+            ],
+        };
+        let mut meta = WavMetadata::from(&list);
+        assert_eq!(meta.items().len(), 3);
+        assert_eq!(meta.title(), Some("Song"));
+        assert_eq!(meta.track(), Some(3));
+        assert_eq!(meta.get_custom("IENC"), Some("v1"));
+        meta.set_artist("Band");
+        meta.set_title("Song 2");
+        meta.set_track(4);
+        assert_eq!(meta.remove(InfoField::Comment), None);
+        assert_eq!(meta.set_custom("I", "x"), Err(MetadataError::InvalidInfoId { id: "I".to_string() }));
+        assert!(meta.set_custom("IENC", "v2").is_ok());
+        let ids: Vec<_> = ListChunk::from(&meta).items.iter().map(|it| format!("{}={}", it.id, it.value)).collect();
+        assert_eq!(ids, vec!["INAM=Song 2", "ITRK=4", "IENC=v2", "IART=Band"]);
+
+        // the header view has the LIST INFO items only, the ID3 tag is merged by `info_items`
+        let mut head = crate::header::WavHeader::new_mono();
+        let mut id3 = Id3Tag::new();
+        id3.album = Some("Album".to_string());
+        head.id3 = Some(id3);
+        head.set_metadata(&meta);
+        assert_eq!(head.metadata(), meta);
+        assert_eq!(head.metadata().album(), None);
+        assert_eq!(WavMetadata::from_items(&head.info_items()).album(), Some("Album"));
+        let list = head.list_chunk.clone();
+        head.set_metadata(&head.metadata());
+        assert_eq!(head.list_chunk, list);
+        head.set_metadata(&WavMetadata::new());
+        assert_eq!(head.list_chunk, None);
+        assert_eq!(head.metadata(), WavMetadata::new());
+
+        // items the metadata does not take are kept when the items are replaced
+        head.list_chunk = Some(ListChunk {
+            items: vec![
+                ListChunkItem { id: "bad!".to_string(), value: "vendor".to_string() },
+                ListChunkItem { id: "INAM".to_string(), value: "Old".to_string() },
+                ListChunkItem { id: "IAB\0".to_string(), value: "raw".to_string() },
+            ],
+        });
+        let mut meta = head.metadata();
+        assert_eq!(meta.items().len(), 1);
+        meta.set_title("New");
+        head.set_metadata(&meta);
+        let ids: Vec<_> = head.list_chunk.as_ref().unwrap().items.iter().map(|it| format!("{}={}", it.id, it.value)).collect();
+        assert_eq!(ids, vec!["INAM=New", "bad!=vendor", "IAB\0=raw"]);
+        head.set_metadata(&WavMetadata::new());
+        assert_eq!(head.list_chunk.as_ref().map(|list| list.items.len()), Some(2));
+
+        // ids that do not fit in four bytes are not written
+        let list = ListChunk {
+            items: vec![
+                ListChunkItem { id: "IAR".to_string(), value: "x".to_string() },
+                ListChunkItem { id: "ITOC".to_string(), value: "toc".to_string() },
+                ListChunkItem { id: "INAME".to_string(), value: "x".to_string() },
+            ],
+        };
+        assert_eq!(list.make_block(), b"ITOC\x04\0\0\0toc\0".to_vec());
+        assert_eq!(WavMetadata::from(&list).get(InfoField::TableOfContents), Some("toc"));
+    }
+}
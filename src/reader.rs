@@ -1,11 +1,24 @@
-/// Wav file reader
+//! Wav file reader
 
 use std::fs::File;
-use std::io::{Cursor, Read};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
 use crate::header::*;
+use crate::g711;
+use crate::adpcm;
+use crate::aiff;
+use crate::caf;
+use crate::au;
+use crate::flac;
+use crate::metadata::{BextChunk, IxmlChunk, CueChunk, SmplChunk, InstChunk, AcidChunk, CartChunk, Id3Tag};
 
 use thiserror::Error;
 
+/// number of bytes read from the data chunk at once
+const READ_BUFFER_SIZE: u64 = 16 * 1024;
+/// chunks that only reserve space and are not kept
+const FILLER_CHUNKS: [&str; 4] = ["JUNK", "junk", "PAD ", "FLLR"];
+
 #[derive(Error, Debug)]
 pub enum DecodeError {
     #[error("Invalid chunk tag, expected '{expected:?}', found '{found:?}'")]
@@ -45,25 +58,22 @@ pub enum DecodeError {
         #[source]
         source: std::io::Error,
     },
+    #[error("Checksum mismatch in {attribute}")]
+    InvalidChecksum {
+        attribute: &'static str,
+    },
+    #[error("Chunk '{id}' has no 64-bit size in the ds64 chunk")]
+    MissingChunkSize {
+        id: String,
+    },
 }
 
 /// Get header and samples from file
 pub fn from_file(file: File) -> Result<WavData, DecodeError> {
-    // read file
-    let mut r: Reader = match Reader::from_file(file) {
-        Err(err) => return Err(err),
-        Ok(r) => r,
-    };
-    // read header
-    let header = match r.read_header() {
-        Err(err) => return Err(err),
-        Ok(head) => head,
-    };
-    // read samples
-    let samples = match r.get_samples_f32() {
-        Err(err) => return Err(err),
-        Ok(samples) => samples,
-    };
+    let mut r = Reader::from_file(file)?;
+    let mut header = r.read_header()?;
+    let samples = r.get_samples_f32()?;
+    r.read_chunks_after_data(&mut header)?;
     Ok(WavData{header, samples})
 }
 
@@ -76,87 +86,429 @@ pub fn from_file_str(file_path: &str) -> Result<WavData, DecodeError> {
     from_file(f)
 }
 
-/// Wav file reader for binary
-pub struct Reader {
-    pub cur: Cursor<Vec<u8>>,
+/// Get header, samples and container from a file of any supported container
+/// (RIFF, RF64, BW64, Wave64, RIFX, AIFF, AIFF-C, CAF, .au or FLAC, detected by the magic bytes)
+pub fn open<P: AsRef<Path>>(path: P) -> Result<(WavData, ContainerKind), DecodeError> {
+    let f = File::open(path).map_err(|err| DecodeError::FileOpen { source: err })?;
+    from_reader(BufReader::new(f))
+}
+
+/// Get header, samples and container from any `Read + Seek` source
+pub fn from_reader<R: Read + Seek>(mut src: R) -> Result<(WavData, ContainerKind), DecodeError> {
+    // FLAC is not chunk based and is decoded at once
+    let start = src.stream_position().map_err(|err| DecodeError::ReadFail { source: err })?;
+    let mut magic = [0; 4];
+    let is_flac = src.read_exact(&mut magic).is_ok() && &magic == b"fLaC";
+    src.seek(SeekFrom::Start(start)).map_err(|err| DecodeError::ReadFail { source: err })?;
+    if is_flac {
+        let mut data = vec![];
+        src.read_to_end(&mut data).map_err(|err| DecodeError::ReadFail { source: err })?;
+        return Ok((flac::from_bytes(&data)?, ContainerKind::Flac));
+    }
+    let mut r = Reader::new(src);
+    let mut header = r.read_header()?;
+    let samples = r.get_samples_f32()?;
+    r.read_chunks_after_data(&mut header)?;
+    Ok((WavData{header, samples}, r.container()))
+}
+
+/// Get header and samples from headerless raw PCM data
+/// A trailing incomplete frame is ignored
+pub fn from_raw(bytes: &[u8], spec: &RawSpec) -> Result<WavData, DecodeError> {
+    let header = spec.header();
+    if header.channels == 0 {
+        return Err(DecodeError::InvalidChunkAttribute {
+            attribute: "channels",
+            expected: 0,
+            found: 0,
+        });
+    }
+    check_sample_format(&header)?;
+    let frame_size = (header.bits_per_sample / 8) as usize * header.channels as usize;
+    let bytes = &bytes[..bytes.len() - bytes.len() % frame_size];
+    let mut samples = Vec::with_capacity(bytes.len() / frame_size * header.channels as usize);
+    decode_samples(&header, spec.layout(), bytes, &mut samples);
+    Ok(WavData { header, samples })
+}
+
+/// Wav file reader for any `Read + Seek` source
+///
+/// The header is parsed by `read_header`, which stops at the beginning of the `data` chunk.
+/// Samples are then decoded incrementally by `read_samples`, so the whole file never has to be in memory.
+pub struct Reader<R = Cursor<Vec<u8>>> {
+    pub cur: R,
     pub header: Option<WavHeader>,
+    /// size of the data chunk in bytes
+    data_size: u64,
+    /// bytes left to read in the data chunk
+    data_remaining: u64,
+    /// sample frames left according to the fact chunk
+    fact_frames: Option<u64>,
+    /// RIFF, RF64, BW64, Wave64, RIFX, AIFF, AIFF-C, CAF or .au
+    pub(crate) container: ContainerKind,
+    /// byte order of the header fields
+    pub(crate) endian: Endian,
+    /// byte order of the samples
+    pub(crate) layout: SampleLayout,
+    /// 64-bit chunk sizes from the ds64 chunk
+    ds64_sizes: Vec<(String, u64)>,
+    /// decoded samples not yet returned
+    buffer: Vec<f32>,
+    buffer_pos: usize,
 }
-impl Reader {    
+
+impl Reader<BufReader<File>> {
     /// Create Reader Object from wav file
-    pub fn from_file(file: File) -> Result<Reader, DecodeError> {
-        let mut data: Vec<u8> = Vec::new();
-        let mut f = file;
-        match f.read_to_end(&mut data) {
-            Ok(_) => {},
-            Err(err) => return Err(DecodeError::ReadFail { source: err }),
-        };
-        Self::from_vec(data)
+    pub fn from_file(file: File) -> Result<Self, DecodeError> {
+        Ok(Reader::new(BufReader::new(file)))
     }
+}
+
+impl Reader<Cursor<Vec<u8>>> {
     /// Crate Reader Object from Vec
-    pub fn from_vec(data: Vec<u8>) -> Result<Reader, DecodeError> {
-        let reader = Reader {
-            cur: Cursor::new(data),
+    pub fn from_vec(data: Vec<u8>) -> Result<Self, DecodeError> {
+        Ok(Reader::new(Cursor::new(data)))
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Create Reader Object from any source
+    pub fn new(cur: R) -> Self {
+        Reader {
+            cur,
             header: None,
-        };
-        Ok(reader)
+            data_size: 0,
+            data_remaining: 0,
+            fact_frames: None,
+            container: ContainerKind::Riff,
+            endian: Endian::Little,
+            layout: SampleLayout::default(),
+            ds64_sizes: Vec::new(),
+            buffer: Vec::new(),
+            buffer_pos: 0,
+        }
+    }
+
+    /// Get the inner source back
+    pub fn into_inner(self) -> R {
+        self.cur
     }
+
     /// Read Wav file header
+    /// The chunks before `data` are parsed and the cursor is left at the start of the samples
     pub fn read_header(&mut self) -> Result<WavHeader, DecodeError> {
         let mut header = WavHeader::new();
-        // RIFF header
+        self.endian = Endian::Little;
+        self.layout = SampleLayout::default();
+        // RIFF header (RF64/BW64 keep the real sizes in the ds64 chunk)
         let riff_tag = self.read_str4();
-        if riff_tag != "RIFF" {
-            return Err(DecodeError::InvalidTag { expected: "RIFF", found: riff_tag.to_string() });
+        match riff_tag.as_str() {
+            "FORM" => return aiff::read_header(self),
+            "caff" => return caf::read_header(self),
+            ".snd" => return au::read_header(self),
+            _ => {},
+        }
+        self.container = match riff_tag.as_str() {
+            "RIFF" => ContainerKind::Riff,
+            "RF64" => ContainerKind::Rf64,
+            "BW64" => ContainerKind::Bw64,
+            "RIFX" => ContainerKind::Rifx,
+            "riff" => ContainerKind::Wave64,
+            _ => return Err(DecodeError::InvalidTag { expected: "RIFF", found: riff_tag }),
+        };
+        self.ds64_sizes.clear();
+        if self.container == ContainerKind::Rifx {
+            // same layout as RIFF with big-endian fields and samples
+            self.endian = Endian::Big;
+            self.layout.endian = Endian::Big;
+        }
+        if self.container == ContainerKind::Wave64 {
+            self.read_w64_header()?;
+        } else {
+            // size
+            let chunk_size = self.read_u32().unwrap_or(0);
+            if chunk_size < 8 {
+                return Err(DecodeError::InvalidChunkAttribute {
+                    attribute: "chunk size",
+                    expected: 7,
+                    found: chunk_size,
+                });
+            }
+            // should be WAVE
+            let wave_tag = self.read_str4();
+            if wave_tag != "WAVE" {
+                return Err(DecodeError::InvalidTag { expected: "WAVE", found: wave_tag });
+            }
+        }
+
+        // read chunks until the data chunk
+        let mut has_fmt = false;
+        self.data_size = 0;
+        self.fact_frames = None;
+        loop {
+            let (chunk_tag, size) = self.read_chunk_header()?;
+            if chunk_tag.is_empty() {
+                break; // no data chunk
+            }
+            let padding = self.container.chunk_padding(size);
+            match chunk_tag.as_str() {
+                "ds64" => self.read_ds64_chunk(size)?,
+                "fmt " => {
+                    self.read_fmt_chunk(size, &mut header)?;
+                    has_fmt = true;
+                },
+                // Wave64 stores the sample length in 64 bits
+                "fact" if size >= 8 && self.container == ContainerKind::Wave64 => {
+                    self.fact_frames = self.read_u64();
+                    self.skip(size - 8 + padding)?;
+                },
+                "fact" if size >= 4 => {
+                    self.fact_frames = self.read_u32().map(|v| v as u64);
+                    self.skip(size - 4 + padding)?;
+                },
+                "data" => {
+                    if !has_fmt {
+                        return Err(DecodeError::InvalidTag { expected: "fmt ", found: chunk_tag });
+                    }
+                    self.data_size = size;
+                    break;
+                },
+                _ if FILLER_CHUNKS.contains(&chunk_tag.as_str()) => self.skip_padded(size)?,
+                _ => {
+                    let data = self.read_chunk_data(size)?;
+                    self.store_chunk(chunk_tag, data, ChunkPosition::BeforeData, &mut header);
+                },
+            }
+        }
+        if !has_fmt {
+            return Err(DecodeError::InvalidTag { expected: "fmt ", found: String::new() });
+        }
+        let data_size = self.data_size;
+        self.start_data(&header, data_size);
+        Ok(header)
+    }
+
+    /// Set the header and get ready to read `data_size` bytes of samples from the current position
+    pub(crate) fn start_data(&mut self, header: &WavHeader, data_size: u64) {
+        self.data_size = data_size;
+        self.data_remaining = data_size;
+        self.buffer.clear();
+        self.buffer_pos = 0;
+        self.header = Some(header.clone());
+    }
+
+    /// Read the chunks after the data chunk into `header` (LIST INFO or unknown chunks)
+    /// The samples that have not been read are skipped. Reading stops at the first broken chunk.
+    pub fn read_chunks_after_data(&mut self, header: &mut WavHeader) -> Result<(), DecodeError> {
+        // the chunks of AIFF, CAF and .au are not kept
+        if !self.container.is_riff() || self.data_size == u64::MAX {
+            return Ok(());
+        }
+        if self.data_remaining > 0 {
+            let padding = self.container.chunk_padding(self.data_size);
+            self.skip(self.data_remaining.saturating_add(padding))?;
+            self.data_remaining = 0;
+            self.buffer.clear();
+            self.buffer_pos = 0;
+        }
+        while let Ok((chunk_tag, size)) = self.read_chunk_header() {
+            if chunk_tag.is_empty() {
+                break;
+            }
+            if FILLER_CHUNKS.contains(&chunk_tag.as_str()) {
+                self.skip_padded(size)?;
+                continue;
+            }
+            let Ok(data) = self.read_chunk_data(size) else { break };
+            self.store_chunk(chunk_tag, data, ChunkPosition::AfterData, header);
         }
-        // size
-        let chunk_size = self.read_u32().unwrap_or(0);
-        if chunk_size < 8 {
+        Ok(())
+    }
+
+    /// Parse a metadata chunk into `header`, or keep it as an unknown chunk
+    fn store_chunk(&mut self, id: String, data: Vec<u8>, position: ChunkPosition, header: &mut WavHeader) {
+        match id.as_str() {
+            // a second LIST INFO is kept as it is
+            "LIST" if data.starts_with(b"INFO") && header.list_chunk.is_none() => {
+                self.analize_list_chunk(data, header);
+                return;
+            },
+            "bext" if header.bext.is_none() => {
+                if let Some(bext) = BextChunk::parse(&data, self.endian) {
+                    header.bext = Some(bext);
+                    return;
+                }
+            },
+            "iXML" if header.ixml.is_none() => {
+                header.ixml = Some(IxmlChunk::parse(&data));
+                return;
+            },
+            "smpl" if header.smpl.is_none() => {
+                if let Some(smpl) = SmplChunk::parse(&data, self.endian) {
+                    header.smpl = Some(smpl);
+                    return;
+                }
+            },
+            "inst" if header.inst.is_none() => {
+                if let Some(inst) = InstChunk::parse(&data) {
+                    header.inst = Some(inst);
+                    return;
+                }
+            },
+            "acid" if header.acid.is_none() => {
+                if let Some(acid) = AcidChunk::parse(&data, self.endian) {
+                    header.acid = Some(acid);
+                    return;
+                }
+            },
+            "cart" if header.cart.is_none() => {
+                if let Some(cart) = CartChunk::parse(&data, self.endian) {
+                    header.cart = Some(cart);
+                    return;
+                }
+            },
+            "id3 " | "ID3 " if header.id3.is_none() => {
+                if let Some(id3) = Id3Tag::parse(&data) {
+                    header.id3 = Some(id3);
+                    return;
+                }
+            },
+            // the adtl list may come before the cue chunk
+            "cue " if header.cue.as_ref().is_none_or(|cue| cue.points.is_empty()) => {
+                if let Some(points) = CueChunk::parse_points(&data, self.endian) {
+                    header.cue.get_or_insert_with(CueChunk::default).points = points;
+                    return;
+                }
+            },
+            "LIST" if data.starts_with(b"adtl") && header.cue.as_ref().is_none_or(|cue| cue.adtl.is_empty()) => {
+                if let Some(adtl) = CueChunk::parse_adtl(&data, self.endian) {
+                    header.cue.get_or_insert_with(CueChunk::default).adtl = adtl;
+                    return;
+                }
+            },
+            _ => {},
+        }
+        header.unknown_chunks.push(UnknownChunk { id, data, position });
+    }
+
+    /// Container of the file (known after `read_header`)
+    pub fn container(&self) -> ContainerKind {
+        self.container
+    }
+
+    /// Read the rest of the Wave64 header (after the first four bytes of the `riff` GUID)
+    fn read_w64_header(&mut self) -> Result<(), DecodeError> {
+        let rest = self.read_bytes::<12>().unwrap_or_default();
+        if rest != W64_RIFF_GUID[4..] {
+            return Err(DecodeError::InvalidTag { expected: "RIFF", found: String::from("riff") });
+        }
+        let file_size = self.read_u64().unwrap_or(0);
+        if file_size < 40 {
             return Err(DecodeError::InvalidChunkAttribute {
                 attribute: "chunk size",
-                expected: 7,
-                found: chunk_size,
+                expected: 39,
+                found: file_size.min(u32::MAX as u64) as u32,
+            });
+        }
+        let wave_guid = self.read_bytes::<16>().unwrap_or_default();
+        if wave_guid != w64_guid("wave") {
+            return Err(DecodeError::InvalidTag { expected: "WAVE", found: w64_chunk_id(&wave_guid) });
+        }
+        Ok(())
+    }
+
+    /// Read the id and the payload size of the next chunk (the id is empty at the end of the file)
+    fn read_chunk_header(&mut self) -> Result<(String, u64), DecodeError> {
+        if self.container == ContainerKind::Wave64 {
+            // the size includes the 24 bytes of the header
+            let Some(guid) = self.read_bytes::<16>() else { return Ok((String::new(), 0)) };
+            let size = self.read_u64().unwrap_or(0).saturating_sub(24);
+            return Ok((w64_chunk_id(&guid), size));
+        }
+        let chunk_tag = self.read_str4();
+        let mut size = self.read_u32().unwrap_or(0) as u64;
+        if size == RF64_SIZE_IN_DS64 as u64 && matches!(self.container, ContainerKind::Rf64 | ContainerKind::Bw64) {
+            size = match self.ds64_size(&chunk_tag) {
+                Some(size) => size,
+                // only the samples may run to the end of the file
+                None if chunk_tag == "data" => u64::MAX,
+                None => return Err(DecodeError::MissingChunkSize { id: chunk_tag }),
+            };
+        }
+        Ok((chunk_tag, size))
+    }
+
+    /// Read the ds64 chunk of RF64/BW64 files
+    fn read_ds64_chunk(&mut self, chunk_size: u64) -> Result<(), DecodeError> {
+        if chunk_size < 28 {
+            return Err(DecodeError::InvalidChunkAttribute {
+                attribute: "ds64 chunk size",
+                expected: 27,
+                found: chunk_size as u32,
             });
         }
-        // should be WAVE
-        let wave_tag = self.read_str4();
-        if  wave_tag != "WAVE" {
-            return Err(DecodeError::InvalidTag { expected: "WAVE", found: riff_tag.to_string() });
+        let _riff_size = self.read_u64();
+        let data_size = self.read_u64().unwrap_or(0);
+        let _sample_count = self.read_u64();
+        let table_length = self.read_u32().unwrap_or(0);
+        self.ds64_sizes.push(("data".to_string(), data_size));
+        // sizes of other chunks larger than 4 GB
+        let mut rest = chunk_size - 28;
+        for _ in 0..table_length {
+            if rest < 12 { break; }
+            let id = self.read_str4();
+            let size = self.read_u64().unwrap_or(0);
+            self.ds64_sizes.push((id, size));
+            rest -= 12;
         }
+        self.skip(rest + self.container.chunk_padding(chunk_size))
+    }
 
-        // check for a possible LIST chunk
-        // if there is one, skip it
-        let _ = self.read_list_chunk(&mut header);
+    /// 64-bit size of a chunk whose size field is 0xFFFFFFFF
+    fn ds64_size(&self, chunk_tag: &str) -> Option<u64> {
+        self.ds64_sizes.iter().find(|(id, _)| id == chunk_tag).map(|(_, size)| *size)
+    }
 
-        // fmt
-        let fmt_tag = self.read_str4();
-        if fmt_tag != "fmt " {
-            return Err(DecodeError::InvalidTag { expected: "fmt ", found: riff_tag.to_string() });
+    /// Read the body of a `fmt ` chunk
+    fn read_fmt_chunk(&mut self, chunk_size: u64, header: &mut WavHeader) -> Result<(), DecodeError> {
+        if chunk_size < 16 {
+            return Err(DecodeError::InvalidChunkAttribute {
+                attribute: "fmt chunk size",
+                expected: 15,
+                found: chunk_size as u32,
+            });
         }
-        let chunk_size = self.read_u32().unwrap_or(0);
         // audio format
         let format_tag = self.read_u16().unwrap_or(0);
         match format_tag {
             0x0001 => header.sample_format = SampleFormat::Int,
+            0x0002 => header.sample_format = SampleFormat::MsAdpcm,
             0x0003 => header.sample_format = SampleFormat::Float,
             0x0006 => header.sample_format = SampleFormat::WaveFromatALaw,
             0x0007 => header.sample_format = SampleFormat::WaveFormatMuLaw,
+            0x0011 => header.sample_format = SampleFormat::ImaAdpcm,
             0xFFFE => header.sample_format = SampleFormat::SubFormat,
             0x0055 => return Err(DecodeError::UnsupportedWav {
                 attribute: "format tag (0x0055: MP3)",
-                expected: &[0x0001, 0x0003, 0x0006, 0x0007, 0xFFFE],
+                expected: &[0x0001, 0x0002, 0x0003, 0x0006, 0x0007, 0x0011, 0xFFFE],
                 found: format_tag as u32,
             }),
             _ => return Err(DecodeError::InvalidChunkAttributeRange {
                 attribute: "format tag",
-                expected: &[0x0001, 0x0003, 0x0006, 0x0007, 0xFFFE],
+                expected: &[0x0001, 0x0002, 0x0003, 0x0006, 0x0007, 0x0011, 0xFFFE],
                 found: format_tag as u32,
             }),
         }
         // channels
         let ch = self.read_u16().unwrap_or(0);
-        if 1 <= ch && ch <= 2 {
-            header.channels = ch;
+        if ch == 0 {
+            return Err(DecodeError::InvalidChunkAttribute {
+                attribute: "channels",
+                expected: 0,
+                found: 0,
+            });
         }
+        header.channels = ch;
         // sample_rate
         header.sample_rate = self.read_u32().unwrap_or(0);
         if header.sample_rate < 32 {
@@ -172,32 +524,93 @@ impl Reader {
             return Err(DecodeError::InvalidChunkAttribute {
                 attribute: "bytes per second",
                 expected: 7,
-                found: header.sample_rate,
+                found: bytes_per_sec,
             });
         }
         // nBlockAlign (channels * bits  / 8)
-        let _data_block_size = self.read_u16().unwrap_or(0);
+        let block_align = self.read_u16().unwrap_or(0);
         // Bits per sample
         let bits_per_sample = self.read_u16().unwrap_or(0);
-        if bits_per_sample < 8 {
+        let mut read_size = 16;
+        if header.sample_format == SampleFormat::ImaAdpcm || header.sample_format == SampleFormat::MsAdpcm {
+            if bits_per_sample != 4 {
+                return Err(DecodeError::UnsupportedWav {
+                    attribute: "bits per ADPCM sample",
+                    expected: &[4],
+                    found: bits_per_sample as u32,
+                });
+            }
+            let is_ms = header.sample_format == SampleFormat::MsAdpcm;
+            // a block holds at least its header (whatever wSamplesPerBlock says)
+            let header_size = if is_ms { 7 } else { 4 } * header.channels.max(1) as u32;
+            if (block_align as u32) < header_size {
+                return Err(DecodeError::InvalidChunkAttribute {
+                    attribute: "block align",
+                    expected: header_size - 1,
+                    found: block_align as u32,
+                });
+            }
+            let mut samples_per_block = if is_ms {
+                adpcm::ms_samples_per_block(block_align, header.channels)
+            } else {
+                adpcm::ima_samples_per_block(block_align, header.channels)
+            };
+            let mut coefs = if is_ms { adpcm::MS_ADPCM_COEFS.to_vec() } else { vec![] };
+            // cbSize + wSamplesPerBlock
+            if chunk_size >= 20 {
+                let _cb_size = self.read_u16().unwrap_or(0);
+                samples_per_block = self.read_u16().unwrap_or(samples_per_block);
+                read_size = 20;
+            }
+            // wNumCoef + coefficient table
+            if is_ms && chunk_size >= 22 {
+                let num_coef = self.read_u16().unwrap_or(0) as u64;
+                read_size = 22;
+                if num_coef > 0 && chunk_size >= read_size + num_coef * 4 {
+                    coefs = (0..num_coef)
+                        .map(|_| (self.read_u16().unwrap_or(0) as i16, self.read_u16().unwrap_or(0) as i16))
+                        .collect();
+                    read_size += num_coef * 4;
+                }
+            }
+            if samples_per_block == 0 {
+                return Err(DecodeError::InvalidChunkAttribute {
+                    attribute: "samples per block",
+                    expected: 0,
+                    found: 0,
+                });
+            }
+            header.adpcm = Some(AdpcmFormat { block_align, samples_per_block, coefs });
+        } else if bits_per_sample < 8 {
             return Err(DecodeError::InvalidChunkAttribute {
                 attribute: "bits per sample",
                 expected: 7,
-                found: header.sample_rate,
+                found: bits_per_sample as u32,
             });
         }
         header.bits_per_sample = bits_per_sample;
-        // println!("chunk_size={}",chunk_size);
-        let pos = self.cur.position() + chunk_size as u64 - 16;
-        self.cur.set_position(pos);
-
-        // check for a possible LIST chunk
-        // if there is one, skip it
-        let _ = self.read_list_chunk(&mut header);
-
-        // set to header
-        self.header = Some(header.clone());
-        Ok(header)
+        // WAVE_FORMAT_EXTENSIBLE
+        if format_tag == 0xFFFE && chunk_size >= 40 {
+            let _cb_size = self.read_u16().unwrap_or(0);
+            let valid_bits_per_sample = self.read_u16().unwrap_or(0);
+            let channel_mask = self.read_u32().unwrap_or(0);
+            let Some(sub_format) = self.read_bytes::<16>() else {
+                return Err(DecodeError::ReadFail { source: std::io::ErrorKind::UnexpectedEof.into() });
+            };
+            let ext = WavExtensible { valid_bits_per_sample, channel_mask, sub_format };
+            // decode samples according to the sub format
+            header.sample_format = match ext.sub_format_tag() {
+                Some(0x0001) => SampleFormat::Int,
+                Some(0x0003) => SampleFormat::Float,
+                Some(0x0006) => SampleFormat::WaveFromatALaw,
+                Some(0x0007) => SampleFormat::WaveFormatMuLaw,
+                _ => SampleFormat::SubFormat,
+            };
+            header.extensible = Some(ext);
+            read_size = 40;
+        }
+        // skip the rest of the chunk
+        self.skip(chunk_size - read_size + self.container.chunk_padding(chunk_size))
     }
 
     /// Read a LIST chunk
@@ -205,36 +618,28 @@ impl Reader {
     /// In the case of `Err()`, the cursor will not have moved
     pub fn read_list_chunk(&mut self, header: &mut WavHeader) -> Result<usize, DecodeError> {
         // keep track of the position, in case we error, we can jump back
-        let begin_position = self.cur.position();
+        let begin_position = self.position()?;
 
         // check the tag
         let info_tag = self.read_str4();
         if info_tag != "LIST" {
-            self.cur.set_position(begin_position);
+            self.seek_to(begin_position)?;
             return Err(DecodeError::InvalidTag {
                 expected: "LIST",
-                found: info_tag.to_string()
+                found: info_tag
             });
         }
-        // retrieve the info size and convert to an usize
+        // retrieve the info size
         let Some(read_size) = self.read_u32() else {
-            self.cur.set_position(begin_position);
-            return Err(DecodeError::ReadFail { source: std::io::Error::new(std::io::ErrorKind::Other, "Unable to read u32") })
-        };
-        let Ok(read_size) = read_size.try_into() else {
-            self.cur.set_position(begin_position);
-            return Err(DecodeError::UnsupportedSystem)
+            self.seek_to(begin_position)?;
+            return Err(DecodeError::ReadFail { source: std::io::Error::other("Unable to read u32") })
         };
-
         // read the data and return it
-        let mut data = vec![0; read_size];
-        match self.cur.read_exact(&mut data) {
-            Ok(_) => {
-                Ok(self.analize_list_chunk(data, header))
-            },
+        match self.read_chunk_data(read_size as u64) {
+            Ok(data) => Ok(self.analize_list_chunk(data, header)),
             Err(err) => {
-                self.cur.set_position(begin_position);
-                Err(DecodeError::ReadFail { source: err })
+                self.seek_to(begin_position)?;
+                Err(err)
             }
         }
     }
@@ -251,31 +656,20 @@ impl Reader {
         if chunk_tag != "INFO" {
             return 0;
         }
-        /*
-        // (ref) https://www.recordingblogs.com/wiki/list-chunk-of-a-wave-file
-        'IART' artist name
-        'INAM' name of the song
-        'IPRD' product name (album name)
-        'IGNR' genre
-        'ICMT' comment
-        'ITRK' track number
-        'ICRD' creation date
-        'ISFT' software
-        'ITOC' CDのTOC(Table of Contents)
-        */
+        // the standard ids are listed in `metadata::InfoField` (`WavHeader::metadata` gives a typed view)
         let mut items = vec![];
         let mut result = 0;
         while cur.position() < data_len {
             // read chunk tag
             let mut chunk_tag = [0u8; 4];
             let chunk_tag = match cur.read_exact(&mut chunk_tag) {
-                Ok(_) => String::from_utf8_lossy(&chunk_tag),
+                Ok(_) => chunk_id(&chunk_tag),
                 Err(_) => break,
             };
             // read info len
             let mut chunk_size = [0u8; 4];
             let chunk_size = match cur.read_exact(&mut chunk_size) {
-                Ok(_) => u32::from_le_bytes(chunk_size),
+                Ok(_) => self.endian.u32_from(chunk_size),
                 Err(_) => break,
             };
             let mut data = vec![0; chunk_size as usize];
@@ -289,7 +683,7 @@ impl Reader {
                 let _ = cur.read_exact(&mut pad);
             }
             let item = ListChunkItem {
-                id: chunk_tag.trim_end_matches('\0').to_string(),
+                id: chunk_tag,
                 value: data.trim_end_matches('\0').to_string(),
             };
             items.push(item);
@@ -299,210 +693,380 @@ impl Reader {
         result
     }
 
-    pub fn get_samples_f32(&mut self) -> Result<Vec<f32>, DecodeError> {
-        let mut result:Vec<f32> = Vec::new();
-        loop {
-            // read chunks
-            let chunk_tag = self.read_str4();
-            if chunk_tag == "" { break; }
-            let size = self.read_u32().unwrap_or(0) as u64;
-            // todo: check tag
-            // println!("[info] tag={:?}::{}", chunk_tag, size);
-            if size == 0 { continue }
-            // data?
-            if chunk_tag != "data" {
-                // skip chunk data + RIFF padding byte if size is odd
-                self.cur.set_position(self.cur.position() + size + (size % 2));
-                continue;
-            }
-            // read wav data
-            let h = &self.header.clone().unwrap();
-            let num_sample = (size / (h.bits_per_sample / 8) as u64) as u64;
-            match h.sample_format {
-                // float
-                SampleFormat::Float => {
-                    match h.bits_per_sample {
-                        32 => {
-                            for _ in 0..num_sample {
-                                let lv = self.read_f32().unwrap_or(0.0);
-                                result.push(lv);
-                            }
-                        },
-                        64 => {
-                            for _ in 0..num_sample {
-                                let lv = self.read_f64().unwrap_or(0.0);
-                                result.push(lv as f32); // down to f32
-                            }
-                        },
-                        _ => return Err(DecodeError::UnsupportedWav {
-                            attribute: "bits per float sample",
-                            expected: &[32, 64],
-                            found: h.bits_per_sample as u32,
-                        }),
-                    }
-                },
-                // int
-                SampleFormat::Int => {
-                    match h.bits_per_sample {
-                        8 => {
-                            for _ in 0..num_sample {
-                                // 0..255
-                                let lv = self.read_u8().unwrap_or(0);
-                                let fv = lv.wrapping_sub(128) as i8 as f32 / (0xFF as f32 / 2.0);
-                                result.push(fv);
-                            }
-                        },
-                        16 => {
-                            for _ in 0..num_sample {
-                                let lv = self.read_i16().unwrap_or(0);
-                                let fv = lv as f32 / (0xFFFF as f32 / 2.0);
-                                result.push(fv);
-                            }
-                        },
-                        24 => {
-                            for _ in 0..num_sample {
-                                let lv = self.read_i24().unwrap_or(0);
-                                let fv = lv as f32 / (0xFFFFFF as f32 / 2.0);
-                                result.push(fv);
-                            }
-                        },
-                        32 => {
-                            for _ in 0..num_sample {
-                                let lv = self.read_i32().unwrap_or(0);
-                                let fv = lv as f32 / (0xFFFFFFFFu32 as f32 / 2.0);
-                                result.push(fv);
-                            }
-                        },
-                        _ => return Err(DecodeError::UnsupportedWav {
-                            attribute: "bits per integer sample",
-                            expected: &[8, 16, 24, 32],
-                            found: h.bits_per_sample as u32,
-                        }),
-                    }
-                },
-                _ => return Err(DecodeError::UnsupportedEncoding),
+    /// Size of the data chunk in bytes
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// Read the next samples of the data chunk into `buf` (interleaved)
+    /// Returns the number of samples written, 0 means the end of the data chunk
+    pub fn read_samples(&mut self, buf: &mut [f32]) -> Result<usize, DecodeError> {
+        let mut count = 0;
+        while count < buf.len() {
+            if self.buffer_pos >= self.buffer.len() && !self.fill_buffer()? {
+                break;
             }
-            // skip RIFF padding byte if data size is odd
-            if size % 2 == 1 {
-                self.cur.set_position(self.cur.position() + 1);
+            let n = (buf.len() - count).min(self.buffer.len() - self.buffer_pos);
+            buf[count..count + n].copy_from_slice(&self.buffer[self.buffer_pos..self.buffer_pos + n]);
+            self.buffer_pos += n;
+            count += n;
+        }
+        Ok(count)
+    }
+
+    /// Read whole frames into `buf` (interleaved)
+    /// `buf` should hold a multiple of the channel count, returns the number of frames read
+    pub fn read_block(&mut self, buf: &mut [f32]) -> Result<usize, DecodeError> {
+        let channels = self.channels();
+        let len = buf.len() - buf.len() % channels;
+        let n = self.read_samples(&mut buf[..len])?;
+        // a partial frame can only remain at the end of a truncated file
+        Ok(n / channels)
+    }
+
+    /// Iterate over the remaining samples frame by frame
+    pub fn frames(&mut self) -> Frames<'_, R> {
+        Frames { reader: self, failed: false }
+    }
+
+    /// Iterate over the remaining samples in interleaved blocks of `frames_per_block` frames
+    /// The last block may be shorter
+    pub fn blocks(&mut self, frames_per_block: usize) -> Blocks<'_, R> {
+        Blocks { reader: self, frames_per_block: frames_per_block.max(1), failed: false }
+    }
+
+    /// Number of channels of the read header (1 before the header is read)
+    pub fn channels(&self) -> usize {
+        match &self.header {
+            Some(h) => h.channels.max(1) as usize,
+            None => 1,
+        }
+    }
+
+    /// Decode the next part of the data chunk into the internal buffer
+    /// Returns false at the end of the data chunk
+    fn fill_buffer(&mut self) -> Result<bool, DecodeError> {
+        self.buffer.clear();
+        self.buffer_pos = 0;
+        if self.data_remaining == 0 {
+            return Ok(false);
+        }
+        let Some(h) = &self.header else { return Ok(false) };
+        check_sample_format(h)?;
+        // read whole samples (or whole blocks)
+        let unit = match &h.adpcm {
+            Some(f) => f.block_align as u64,
+            None => (h.bits_per_sample / 8) as u64,
+        };
+        let read_size = self.data_remaining.min((READ_BUFFER_SIZE - READ_BUFFER_SIZE % unit).max(unit));
+        let mut bytes = vec![0u8; read_size as usize];
+        let n = self.read_fully(&mut bytes)?;
+        if n < bytes.len() {
+            // the file ends before the data chunk
+            self.data_remaining = 0;
+        } else {
+            self.data_remaining -= n as u64;
+            // skip the padding after the data chunk
+            if self.data_remaining == 0 {
+                let _ = self.skip(self.container.chunk_padding(self.data_size));
             }
         }
+        let Some(h) = &self.header else { return Ok(false) };
+        decode_samples(h, self.layout, &bytes[..n], &mut self.buffer);
+        // drop the padding of the last block
+        if let (Some(frames), Some(_)) = (self.fact_frames, &h.adpcm) {
+            let channels = h.channels.max(1) as u64;
+            let len = (self.buffer.len() as u64).min(frames * channels);
+            self.buffer.truncate(len as usize);
+            self.fact_frames = Some(frames - len / channels);
+        }
+        Ok(!self.buffer.is_empty())
+    }
+
+    /// Read all (remaining) samples as f32
+    pub fn get_samples_f32(&mut self) -> Result<Vec<f32>, DecodeError> {
+        if self.header.is_none() {
+            self.read_header()?;
+        }
+        let mut result: Vec<f32> = Vec::new();
+        let mut buf = vec![0f32; 4096];
+        loop {
+            let n = self.read_samples(&mut buf)?;
+            if n == 0 { break; }
+            result.extend_from_slice(&buf[..n]);
+        }
         Ok(result)
     }
 
+    /// Read the payload of a chunk (and its padding byte)
+    pub(crate) fn read_chunk_data(&mut self, size: u64) -> Result<Vec<u8>, DecodeError> {
+        let Ok(size) = usize::try_from(size) else {
+            return Err(DecodeError::UnsupportedSystem)
+        };
+        // a broken size must not allocate more than the source has
+        let mut data = Vec::new();
+        if let Err(err) = (&mut self.cur).take(size as u64).read_to_end(&mut data) {
+            return Err(DecodeError::ReadFail { source: err });
+        }
+        if data.len() < size {
+            return Err(DecodeError::ReadFail { source: std::io::ErrorKind::UnexpectedEof.into() });
+        }
+        self.skip(self.container.chunk_padding(size as u64))?;
+        Ok(data)
+    }
+
+    /// Read as many bytes as possible, stopping only at the end of the source
+    fn read_fully(&mut self, buf: &mut [u8]) -> Result<usize, DecodeError> {
+        let mut total = 0;
+        while total < buf.len() {
+            match self.cur.read(&mut buf[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(DecodeError::ReadFail { source: err }),
+            }
+        }
+        Ok(total)
+    }
+
+    pub(crate) fn skip(&mut self, size: u64) -> Result<(), DecodeError> {
+        // a size that does not fit in i64 would seek backwards
+        let Ok(offset) = i64::try_from(size) else {
+            return Err(DecodeError::ReadFail { source: std::io::ErrorKind::UnexpectedEof.into() });
+        };
+        match self.cur.seek(SeekFrom::Current(offset)) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(DecodeError::ReadFail { source: err }),
+        }
+    }
+
+    /// Skip a chunk body and its padding byte
+    fn skip_padded(&mut self, size: u64) -> Result<(), DecodeError> {
+        match size.checked_add(self.container.chunk_padding(size)) {
+            Some(total) => self.skip(total),
+            None => Err(DecodeError::ReadFail { source: std::io::ErrorKind::UnexpectedEof.into() }),
+        }
+    }
+
+    pub(crate) fn position(&mut self) -> Result<u64, DecodeError> {
+        match self.cur.stream_position() {
+            Ok(pos) => Ok(pos),
+            Err(err) => Err(DecodeError::ReadFail { source: err }),
+        }
+    }
+
+    pub(crate) fn seek_to(&mut self, pos: u64) -> Result<(), DecodeError> {
+        match self.cur.seek(SeekFrom::Start(pos)) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(DecodeError::ReadFail { source: err }),
+        }
+    }
+
+    pub(crate) fn read_bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let mut buf = [0u8; N];
+        match self.cur.read_exact(&mut buf) {
+            Ok(_) => Some(buf),
+            Err(_) => None,
+        }
+    }
+
+    /// read bytes in little-endian order (reversed for big-endian headers)
+    fn read_ordered<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let mut buf = self.read_bytes::<N>()?;
+        if self.endian == Endian::Big {
+            buf.reverse();
+        }
+        Some(buf)
+    }
+
     pub fn read_str4(&mut self) -> String {
-        let mut buf = [0u8; 4];
-        match self.cur.read(&mut buf) {
-            Ok(sz) => {
-                if sz < 4 {
-                    return String::from("");
-                }
-            },
-            Err(_) => return String::from(""),
+        match self.read_bytes::<4>() {
+            Some(buf) => chunk_id(&buf),
+            None => String::from(""),
         }
-        let s = String::from_utf8_lossy(&buf);
-        String::from(s)
     }
 
     pub fn read_f32(&mut self) -> Option<f32> {
-        match self.read_u32() {
-            Some(v) => Some(f32::from_bits(v)),
-            None => None,
-        }
+        self.read_u32().map(f32::from_bits)
     }
 
     pub fn read_f64(&mut self) -> Option<f64> {
-        match self.read_u64() {
-            Some(v) => Some(f64::from_bits(v)),
-            None => None,
-        }
+        self.read_u64().map(f64::from_bits)
     }
 
     pub fn read_u64(&mut self) -> Option<u64> {
-        let mut buf = [0u8; 8];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        Some(u64::from_le_bytes(buf))
+        self.read_ordered::<8>().map(u64::from_le_bytes)
     }
 
     pub fn read_u32(&mut self) -> Option<u32> {
-        let mut buf = [0u8; 4];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        Some(u32::from_le_bytes(buf))
+        self.read_ordered::<4>().map(u32::from_le_bytes)
     }
 
     pub fn read_i32(&mut self) -> Option<i32> {
-        let mut buf = [0u8; 4];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        Some(i32::from_le_bytes(buf))
+        self.read_ordered::<4>().map(i32::from_le_bytes)
     }
 
     pub fn read_u24(&mut self) -> Option<u32> {
-        let mut buf = [0u8; 3];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        let result = 
-            (buf[0] as u32) << 0 | 
-            (buf[1] as u32) << 8 |
-            (buf[2] as u32) << 16;
-        Some(result)
+        let buf = self.read_ordered::<3>()?;
+        Some(u32::from_le_bytes([buf[0], buf[1], buf[2], 0]))
     }
 
     pub fn read_i24(&mut self) -> Option<i32> {
-        let mut buf = [0u8; 3];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
+        let buf = self.read_ordered::<3>()?;
         let buf4 = [0, buf[0], buf[1], buf[2]];
         Some(i32::from_le_bytes(buf4) >> 8)
     }
 
     pub fn read_u16(&mut self) -> Option<u16> {
-        let mut buf = [0u8; 2];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        let result = 
-            (buf[0] as u16) << 0 |
-            (buf[1] as u16) << 8;
-        Some(result)
+        self.read_ordered::<2>().map(u16::from_le_bytes)
     }
 
     pub fn read_i16(&mut self) -> Option<i16> {
-        let mut buf = [0u8; 2];
-        match self.cur.read(&mut buf) {
-            Ok(v) => v,
-            Err(_) => return None,
-        };
-        Some(i16::from_le_bytes(buf))
+        self.read_ordered::<2>().map(i16::from_le_bytes)
     }
 
     pub fn read_u8(&mut self) -> Option<u8> {
-        let mut buf = [0u8; 1];
-        match self.cur.read(&mut buf) {
-            Ok(_) => Some(buf[0]),
-            Err(_) => None,
+        self.read_bytes::<1>().map(|b| b[0])
+    }
+}
+
+/// Iterator over frames, created by `Reader::frames`
+/// An error is returned once, then the iteration ends
+pub struct Frames<'a, R> {
+    reader: &'a mut Reader<R>,
+    failed: bool,
+}
+
+impl<R: Read + Seek> Iterator for Frames<'_, R> {
+    type Item = Result<Vec<f32>, DecodeError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut frame = vec![0f32; self.reader.channels()];
+        match self.reader.read_block(&mut frame) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(frame)),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+/// Iterator over interleaved blocks, created by `Reader::blocks`
+/// An error is returned once, then the iteration ends
+pub struct Blocks<'a, R> {
+    reader: &'a mut Reader<R>,
+    frames_per_block: usize,
+    failed: bool,
+}
+
+impl<R: Read + Seek> Iterator for Blocks<'_, R> {
+    type Item = Result<Vec<f32>, DecodeError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut block = vec![0f32; self.frames_per_block * self.reader.channels()];
+        match self.reader.read_block(&mut block) {
+            Ok(0) => None,
+            Ok(frames) => {
+                block.truncate(frames * self.reader.channels());
+                Some(Ok(block))
+            },
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            },
         }
     }
 }
 
+/// Check that samples of this format can be decoded
+fn check_sample_format(h: &WavHeader) -> Result<(), DecodeError> {
+    match h.sample_format {
+        SampleFormat::Float => match h.bits_per_sample {
+            32 | 64 => Ok(()),
+            _ => Err(DecodeError::UnsupportedWav {
+                attribute: "bits per float sample",
+                expected: &[32, 64],
+                found: h.bits_per_sample as u32,
+            }),
+        },
+        SampleFormat::Int => match h.bits_per_sample {
+            8 | 16 | 24 | 32 => Ok(()),
+            _ => Err(DecodeError::UnsupportedWav {
+                attribute: "bits per integer sample",
+                expected: &[8, 16, 24, 32],
+                found: h.bits_per_sample as u32,
+            }),
+        },
+        SampleFormat::WaveFromatALaw | SampleFormat::WaveFormatMuLaw => match h.bits_per_sample {
+            8 => Ok(()),
+            _ => Err(DecodeError::UnsupportedWav {
+                attribute: "bits per G.711 sample",
+                expected: &[8],
+                found: h.bits_per_sample as u32,
+            }),
+        },
+        SampleFormat::ImaAdpcm | SampleFormat::MsAdpcm if h.bits_per_sample == 4 && h.adpcm.is_some() => Ok(()),
+        _ => Err(DecodeError::UnsupportedEncoding),
+    }
+}
+
+/// Decode little endian sample bytes into f32 samples
+/// The format must be checked by `check_sample_format` beforehand
+fn decode_samples(h: &WavHeader, layout: SampleLayout, bytes: &[u8], out: &mut Vec<f32>) {
+    if let Some(f) = &h.adpcm {
+        for block in bytes.chunks(f.block_align as usize) {
+            if h.sample_format == SampleFormat::MsAdpcm {
+                adpcm::decode_ms_block(block, h.channels, f.samples_per_block, &f.coefs, out);
+            } else {
+                adpcm::decode_ima_block(block, h.channels, f.samples_per_block, out);
+            }
+        }
+        return;
+    }
+    let size = (h.bits_per_sample / 8) as usize;
+    let mut buf = [0u8; 8];
+    for sample in bytes.chunks_exact(size) {
+        // little-endian copy of the sample
+        let b = &mut buf[..size];
+        b.copy_from_slice(sample);
+        if layout.endian == Endian::Big {
+            b.reverse();
+        }
+        let v = match (h.sample_format, h.bits_per_sample) {
+            (SampleFormat::Float, 32) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            (SampleFormat::Float, 64) => {
+                f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32 // down to f32
+            },
+            // -128..127 (AIFF)
+            (SampleFormat::Int, 8) if layout.signed_8bit => b[0] as i8 as f32 / (0xFF as f32 / 2.0),
+            // 0..255
+            (SampleFormat::Int, 8) => b[0].wrapping_sub(128) as i8 as f32 / (0xFF as f32 / 2.0),
+            (SampleFormat::Int, 16) => i16::from_le_bytes([b[0], b[1]]) as f32 / (0xFFFF as f32 / 2.0),
+            (SampleFormat::Int, 24) => {
+                (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / (0xFFFFFF as f32 / 2.0)
+            },
+            (SampleFormat::Int, 32) => {
+                i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / (0xFFFFFFFFu32 as f32 / 2.0)
+            },
+            (SampleFormat::WaveFromatALaw, 8) => g711::alaw_to_linear(b[0]) as f32 / 32768.0,
+            (SampleFormat::WaveFormatMuLaw, 8) => g711::ulaw_to_linear(b[0]) as f32 / 32768.0,
+            _ => 0.0,
+        };
+        out.push(v);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
-    
+
     fn read_it() {
         let mut r = Reader::from_vec(vec![1,0]).unwrap();
         assert_eq!(Some(1), r.read_i16());
@@ -519,5 +1083,364 @@ mod tests {
         let mut r = Reader::from_vec(vec![0xFF,0xFF, 0xFF]).unwrap();
         assert_eq!(Some(-1), r.read_i24());
     }
-}
 
+    #[test]
+    fn read_samples_in_blocks() {
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        let samples: Vec<f32> = (0..50_000).map(|i| ((i % 100) as f32 / 100.0) - 0.5).collect();
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        let all = Reader::from_vec(bytes.clone()).unwrap().get_samples_f32().unwrap();
+        assert_eq!(all.len(), samples.len());
+
+        // read with a small bounded buffer
+        let mut r = Reader::new(Cursor::new(bytes));
+        r.read_header().unwrap();
+        assert_eq!(r.data_size(), samples.len() as u64 * 2);
+        let mut buf = [0f32; 333];
+        let mut streamed = vec![];
+        loop {
+            let n = r.read_samples(&mut buf).unwrap();
+            if n == 0 { break; }
+            streamed.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(streamed, all);
+    }
+
+    #[test]
+    fn read_frames_and_blocks() {
+        let head = WavHeader::new_stereo();
+        let samples: Vec<f32> = (0..2_000).map(|i| i as f32 / 2_000.0).collect();
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+
+        // frame by frame
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        r.read_header().unwrap();
+        let frames: Vec<Vec<f32>> = r.frames().map(|f| f.unwrap()).collect();
+        assert_eq!(frames.len(), 1_000);
+        assert_eq!(frames[1], vec![samples[2], samples[3]]);
+
+        // fixed size blocks, the last one is shorter
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        r.read_header().unwrap();
+        let blocks: Vec<Vec<f32>> = r.blocks(300).map(|b| b.unwrap()).collect();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].len(), 600);
+        assert_eq!(blocks[3].len(), 200);
+        assert_eq!(blocks.concat(), samples);
+
+        // read_block never splits a frame
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        let mut buf = [0f32; 5];
+        assert_eq!(r.read_block(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..4], &samples[..4]);
+
+        // an error ends the iteration (12-bit samples can not be decoded)
+        let mut bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        bytes[34] = 12;
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        r.read_header().unwrap();
+        let frames: Vec<_> = r.frames().collect();
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].is_err());
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.blocks(300).filter_map(Result::ok).count(), 0);
+    }
+
+    #[test]
+    fn read_extensible() {
+        // 24-bit int is written as WAVE_FORMAT_EXTENSIBLE
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 24;
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        assert_eq!(&bytes[20..22], &[0xFE, 0xFF]);
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::Int);
+        assert_eq!(h.bits_per_sample, 24);
+        let ext = h.extensible.unwrap();
+        assert_eq!(ext.valid_bits_per_sample, 24);
+        assert_eq!(ext.channel_mask, 0x3);
+        assert_eq!(ext.sub_format_tag(), Some(0x0001));
+        for (a, b) in r.get_samples_f32().unwrap().iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        // float sub format
+        let mut head = WavHeader::new_mono();
+        head.extensible = Some(WavExtensible::new(&head));
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::Float);
+        assert_eq!(h.extensible.unwrap().sub_format_tag(), Some(0x0003));
+        assert_eq!(r.get_samples_f32().unwrap(), samples);
+    }
+
+    #[test]
+    fn read_multichannel() {
+        // 5.1
+        let mut head = WavHeader::new_mono();
+        head.channels = 6;
+        let samples: Vec<f32> = (0..60).map(|i| i as f32 / 60.0).collect();
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.channels, 6);
+        assert_eq!(h.extensible.unwrap().channel_mask, 0x3F);
+        let frames: Vec<Vec<f32>> = r.frames().map(|f| f.unwrap()).collect();
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[1], samples[6..12].to_vec());
+    }
+
+    #[test]
+    fn read_ms_adpcm() {
+        // mono, 8 kHz, one 14 byte block with a custom coefficient table
+        let mut data = vec![];
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&26u32.to_le_bytes());
+        for v in [2u16, 1] { data.extend_from_slice(&v.to_le_bytes()); }
+        data.extend_from_slice(&8_000u32.to_le_bytes());
+        data.extend_from_slice(&8_000u32.to_le_bytes());
+        for v in [14u16, 4, 8, 16, 1, 256, 0] { data.extend_from_slice(&v.to_le_bytes()); }
+        data.extend_from_slice(b"fact");
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&15u32.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&14u32.to_le_bytes());
+        data.push(0); // predictor
+        for v in [16i16, 0, 0] { data.extend_from_slice(&v.to_le_bytes()); }
+        data.extend_from_slice(&[0x11; 7]);
+        let len = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&len.to_le_bytes());
+
+        let mut r = Reader::from_vec(data.clone()).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::MsAdpcm);
+        assert_eq!(h.bits_per_sample, 4);
+        let f = h.adpcm.unwrap();
+        assert_eq!(f.samples_per_block, 16);
+        assert_eq!(f.coefs, vec![(256, 0)]);
+        // 16 samples in the block, 15 according to the fact chunk
+        let samples = r.get_samples_f32().unwrap();
+        assert_eq!(samples.len(), 15);
+        assert_eq!(samples[2], 16.0 / 32768.0);
+        assert!(samples.windows(2).skip(1).all(|w| w[1] > w[0]));
+
+        // without the fact chunk every block is cut to wSamplesPerBlock
+        let mut short = data[..46].to_vec();
+        short[38..40].copy_from_slice(&12u16.to_le_bytes());
+        short.extend_from_slice(&data[58..]);
+        let len = short.len() as u32 - 8;
+        short[4..8].copy_from_slice(&len.to_le_bytes());
+        let mut r = Reader::from_vec(short.clone()).unwrap();
+        assert_eq!(r.read_header().unwrap().adpcm.unwrap().samples_per_block, 12);
+        assert_eq!(r.get_samples_f32().unwrap(), samples[..12].to_vec());
+        // a block must hold its header
+        short[32..34].copy_from_slice(&6u16.to_le_bytes());
+        let mut r = Reader::from_vec(short).unwrap();
+        assert!(matches!(r.read_header(), Err(DecodeError::InvalidChunkAttribute { attribute: "block align", .. })));
+    }
+
+    #[test]
+    fn read_ima_adpcm_block_layout() {
+        // mono IMA ADPCM with a 20 byte fmt chunk and no fact chunk
+        let ima = |block_align: u16, samples_per_block: u16, blocks: usize| {
+            let mut data = vec![];
+            data.extend_from_slice(b"RIFF");
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(b"WAVEfmt ");
+            data.extend_from_slice(&20u32.to_le_bytes());
+            for v in [0x11u16, 1] { data.extend_from_slice(&v.to_le_bytes()); }
+            data.extend_from_slice(&8_000u32.to_le_bytes());
+            data.extend_from_slice(&4_000u32.to_le_bytes());
+            for v in [block_align, 4, 2, samples_per_block] { data.extend_from_slice(&v.to_le_bytes()); }
+            data.extend_from_slice(b"data");
+            let size = block_align as usize * blocks;
+            data.extend_from_slice(&(size as u32).to_le_bytes());
+            data.resize(data.len() + size, 0);
+            let len = data.len() as u32 - 8;
+            data[4..8].copy_from_slice(&len.to_le_bytes());
+            data
+        };
+        // a block must hold its header
+        for block_align in [0, 3] {
+            let mut r = Reader::from_vec(ima(block_align, 10, 0)).unwrap();
+            assert!(matches!(r.read_header(), Err(DecodeError::InvalidChunkAttribute { attribute: "block align", .. })));
+        }
+        let mut r = Reader::from_vec(ima(256, 0, 1)).unwrap();
+        assert!(r.read_header().is_err());
+        // every block is cut to wSamplesPerBlock
+        let mut r = Reader::from_vec(ima(256, 10, 2)).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.get_samples_f32().unwrap().len(), 20);
+        let (wav, _) = from_reader(Cursor::new(ima(256, 505, 2))).unwrap();
+        assert_eq!(wav.samples.len(), 1_010);
+    }
+
+    #[test]
+    fn skip_unknown_chunks_before_fmt() {
+        let head = WavHeader::new_mono();
+        let bytes = crate::writer::to_bytes(&head, &[0.25, -0.25]).unwrap();
+        // insert a JUNK chunk with odd size (and padding) after "WAVE"
+        let mut data = bytes[0..12].to_vec();
+        data.extend_from_slice(b"JUNK");
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 3, 0]);
+        data.extend_from_slice(&bytes[12..]);
+        let mut r = Reader::from_vec(data).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::Float);
+        assert_eq!(r.get_samples_f32().unwrap(), vec![0.25, -0.25]);
+    }
+
+    #[test]
+    fn raw_import_and_export() {
+        // big-endian 16-bit stereo with a trailing incomplete frame
+        let mut spec = RawSpec::new(16_000, 2, 16);
+        spec.endian = Endian::Big;
+        let wav = from_raw(&[0x40, 0x00, 0xC0, 0x00, 0x20, 0x00, 0xE0, 0x00, 0x7F], &spec).unwrap();
+        assert_eq!(wav.header.sample_rate, 16_000);
+        assert_eq!(wav.header.channels, 2);
+        assert_eq!(wav.samples.len(), 4);
+        assert!((wav.samples[0] - 0.5).abs() < 1e-4 && (wav.samples[1] + 0.5).abs() < 1e-4);
+        let bytes = crate::writer::to_raw(&wav, &spec).unwrap();
+        assert_eq!(bytes, [0x40, 0x00, 0xC0, 0x00, 0x20, 0x00, 0xE0, 0x00]);
+
+        // signed 8-bit
+        let mut spec = RawSpec::new(8_000, 1, 8);
+        spec.signed_8bit = true;
+        let wav = from_raw(&[0x00, 0x40, 0xC0], &spec).unwrap();
+        assert_eq!(wav.samples[0], 0.0);
+        assert!(wav.samples[1] > 0.5 && wav.samples[2] < -0.5);
+
+        // the raw samples are wrapped into a WAV file
+        let bytes = crate::writer::to_bytes(&wav.header, &wav.samples).unwrap();
+        let mut r = Reader::from_vec(bytes).unwrap();
+        assert_eq!(r.read_header().unwrap(), wav.header);
+        assert_eq!(r.get_samples_f32().unwrap().len(), 3);
+
+        assert!(from_raw(&[0; 4], &RawSpec::new(8_000, 1, 12)).is_err());
+    }
+
+    #[test]
+    fn keep_unknown_chunks() {
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.unknown_chunks = vec![
+            UnknownChunk { id: "bext".to_string(), data: vec![1, 2, 3], position: ChunkPosition::BeforeData },
+            UnknownChunk { id: "LIST".to_string(), data: b"adtlnote".to_vec(), position: ChunkPosition::BeforeData },
+            UnknownChunk { id: "cue ".to_string(), data: vec![4; 4], position: ChunkPosition::AfterData },
+        ];
+        let samples = vec![0.5, -0.5, 0.25];
+        let bytes = crate::writer::to_bytes(&head, &samples).unwrap();
+        // RIFF(12) + fmt(24) + bext(8 + 3 + 1) + LIST(8 + 8) + data(8 + 6) + cue(8 + 4)
+        assert_eq!(bytes.len(), 12 + 24 + 12 + 16 + 14 + 12);
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[36..40], b"bext");
+        assert_eq!(&bytes[78..82], b"cue ");
+
+        // a JUNK chunk is dropped
+        let mut data = bytes[..36].to_vec();
+        data.extend_from_slice(b"JUNK\x02\0\0\0\0\0");
+        data.extend_from_slice(&bytes[36..]);
+        let (wav, _) = from_reader(Cursor::new(data)).unwrap();
+        assert_eq!(wav.header.unknown_chunks, head.unknown_chunks);
+        assert_eq!(crate::writer::to_bytes(&wav.header, &wav.samples).unwrap(), bytes);
+
+        // streaming writer
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = crate::writer::WavWriter::new(&mut cur, &head).unwrap();
+        w.write_samples(&samples).unwrap();
+        w.finalize().unwrap();
+        let (wav, _) = from_reader(Cursor::new(cur.into_inner())).unwrap();
+        assert_eq!(wav.header.unknown_chunks, head.unknown_chunks);
+        assert_eq!(wav.samples.len(), 3);
+
+        // any four bytes are kept as the id
+        let mut raw = head.clone();
+        raw.unknown_chunks = vec![
+            UnknownChunk { id: chunk_id(b"\xFFabc"), data: vec![5; 2], position: ChunkPosition::BeforeData },
+        ];
+        let bytes = crate::writer::to_bytes(&raw, &samples).unwrap();
+        assert_eq!(&bytes[36..40], b"\xFFabc");
+        let (wav, _) = from_reader(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(wav.header.unknown_chunks, raw.unknown_chunks);
+        assert_eq!(crate::writer::to_bytes(&wav.header, &wav.samples).unwrap(), bytes);
+
+        // ids that do not fit in four bytes are rejected before the samples are written
+        raw.unknown_chunks[0].position = ChunkPosition::AfterData;
+        for id in ["ab", "abcde", "\u{100}abc"] {
+            raw.unknown_chunks[0].id = id.to_string();
+            assert!(matches!(
+                crate::writer::to_bytes(&raw, &samples),
+                Err(crate::writer::EncoderError::InvalidChunkId { .. })
+            ));
+            assert!(crate::writer::WavWriter::new(Cursor::new(Vec::new()), &raw).is_err());
+        }
+        // Wave64 also takes a GUID
+        raw.unknown_chunks[0].id = "00112233445566778899aabbccddeeff".to_string();
+        let bytes = crate::writer::to_bytes_as(&raw, &samples, ContainerKind::Wave64).unwrap();
+        let (wav, _) = from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(wav.header.unknown_chunks, raw.unknown_chunks);
+        assert!(crate::writer::to_bytes(&raw, &samples).is_err());
+    }
+
+    #[test]
+    fn detect_container() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        for kind in [
+            ContainerKind::Riff, ContainerKind::Rf64, ContainerKind::Bw64, ContainerKind::Wave64,
+            ContainerKind::Rifx, ContainerKind::Aiff, ContainerKind::Caf, ContainerKind::Au, ContainerKind::Flac,
+        ] {
+            let bytes = crate::writer::to_bytes_as(&head, &samples, kind).unwrap();
+            let (wav, container) = from_reader(Cursor::new(bytes.clone())).unwrap();
+            assert_eq!(container, kind);
+            assert_eq!(wav.header.channels, 2);
+            assert_eq!(wav.header.bits_per_sample, 16);
+            for (a, b) in wav.samples.iter().zip(samples.iter()) {
+                assert!((a - b).abs() < 1e-4, "{kind:?}: {a} != {b}");
+            }
+            // written back in the same container
+            assert_eq!(crate::writer::to_bytes_as(&wav.header, &wav.samples, container).unwrap(), bytes);
+        }
+        assert!(from_reader(Cursor::new(b"OggS\0\0\0\0".to_vec())).is_err());
+    }
+
+    #[test]
+    fn rf64_chunk_without_ds64_size() {
+        let head = WavHeader::new_mono();
+        let bytes = crate::writer::to_bytes_as(&head, &[0.25, -0.25], ContainerKind::Rf64).unwrap();
+        let data_pos = bytes.windows(4).position(|w| w == b"data").unwrap();
+        // a JUNK chunk whose size is only in the ds64 table, which has no entry for it
+        let mut junk = bytes[..data_pos].to_vec();
+        junk.extend_from_slice(b"JUNK");
+        junk.extend_from_slice(&u32::MAX.to_le_bytes());
+        junk.extend_from_slice(&bytes[data_pos..]);
+        assert!(matches!(
+            from_reader(Cursor::new(junk)),
+            Err(DecodeError::MissingChunkSize { id }) if id == "JUNK"
+        ));
+        // after the samples the broken chunk ends the chunk list
+        let mut after = bytes.clone();
+        after.extend_from_slice(b"JUNK");
+        after.extend_from_slice(&u32::MAX.to_le_bytes());
+        let (wav, _) = from_reader(Cursor::new(after)).unwrap();
+        assert_eq!(wav.samples.len(), 2);
+        // the samples may still run to the end of the file
+        let mut open = bytes.clone();
+        open[data_pos + 4..data_pos + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(from_reader(Cursor::new(open)).is_ok());
+    }
+}
@@ -8,17 +8,19 @@ pub fn linear(samples: Vec<f32>, channels: u16, cur_rate: u32, new_rate: u32) ->
         return samples.clone();
     }
     // check channels
-    if channels == 1 {
+    if channels <= 1 {
         if cur_rate < new_rate {
             return linear_upsamle(samples, cur_rate, new_rate);
         } else {
             return linear_downsample(samples, cur_rate, new_rate);
         }
     }
-    let (mut l_samples, mut r_samples) = utils::split_stereo_wave(samples);
-    l_samples = linear(l_samples, 1, cur_rate, new_rate);
-    r_samples = linear(r_samples, 1, cur_rate, new_rate);
-    utils::join_stereo_wave(l_samples, r_samples)
+    // resample each channel
+    let channel_samples: Vec<Vec<f32>> = utils::deinterleave(&samples, channels)
+        .into_iter()
+        .map(|ch| linear(ch, 1, cur_rate, new_rate))
+        .collect();
+    utils::interleave(&channel_samples)
 }
 
 fn linear_upsamle(samples: Vec<f32>, cur_rate: u32, new_rate: u32) -> Vec<f32> {
@@ -1,3 +1,5 @@
+use crate::metadata::{CueChunk, AdtlItem};
+
 /// Wav file Splitter
 #[derive(Debug,Copy,Clone,PartialEq)]
 pub struct WavSplitRange {
@@ -14,6 +16,12 @@ pub struct WavSplitOption {
     pub margin: f32,
 }
 
+impl Default for WavSplitOption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WavSplitOption {
     pub fn new() -> Self {
         Self {
@@ -27,9 +35,9 @@ impl WavSplitOption {
 }
 
 /// normalize samples
-pub fn normalize_i(samples: &Vec<f32>) -> Vec<i16> {
+pub fn normalize_i(samples: &[f32]) -> Vec<i16> {
     let mut result: Vec<i16> = Vec::with_capacity(samples.len());
-    let imax = std::i16::MAX as f32;
+    let imax = i16::MAX as f32;
     // get max value
     let mut max: f32 = 0.0;
     for v in samples.iter() {
@@ -37,16 +45,16 @@ pub fn normalize_i(samples: &Vec<f32>) -> Vec<i16> {
         if av > max { max = av; }
     }
     // mul
-    let r = 1.0 / max as f32;
-    for i in 0..samples.len() {
-        let v = r * samples[i];
+    let r = 1.0 / max;
+    for sv in samples.iter() {
+        let v = r * sv;
         let iv = (v * imax) as i16;
         result.push(iv);
     }
     result
 }
 
-pub fn normalize_f(samples: &Vec<f32>) -> Vec<f32> {
+pub fn normalize_f(samples: &[f32]) -> Vec<f32> {
     let mut result: Vec<f32> = Vec::with_capacity(samples.len());
     let mut max = 0.0;
     for v in samples.iter() {
@@ -54,8 +62,8 @@ pub fn normalize_f(samples: &Vec<f32>) -> Vec<f32> {
         if av > max { max = av; } 
     }
     let r = 1.0 / max;
-    for i in 0..samples.len() {
-        let v = r * samples[i];
+    for sv in samples.iter() {
+        let v = r * sv;
         result.push(v);
     }
     result
@@ -69,15 +77,15 @@ fn get_min(a: isize, b: isize) -> isize {
 }
 
 /// split wave data
-pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOption) -> Vec<WavSplitRange> {
+pub fn split_samples(samples: &mut [f32], sample_rate: u32, opt: &WavSplitOption) -> Vec<WavSplitRange> {
     let silence_thresh = opt.min_silence_level;
-    let silence_thresh_i = (silence_thresh * std::i16::MAX as f32) as i16;
+    let silence_thresh_i = (silence_thresh * i16::MAX as f32) as i16;
     let min_silence_len = (sample_rate as f32 * opt.min_silence_duration) as usize;
     let min_keep_len = (sample_rate as f32 * opt.min_keep_duration) as usize;
     let margin_len = (sample_rate as f32 * opt.margin) as usize;
     let mut result = vec![];
     let samples_len = samples.len();
-    let mut rms_size: u32 = (sample_rate / 300) as u32;
+    let mut rms_size: u32 = sample_rate / 300;
     if rms_size < 5 { rms_size = 5; }
     if opt.is_debug {
         println!("silence_thresh={}", silence_thresh);
@@ -94,7 +102,7 @@ pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOpt
     // detect silence
     let mut th_vec:Vec<i16> = Vec::with_capacity(samples_len);
     let mut remain :usize = 0;
-    let v_on = std::i16::MAX / 2;
+    let v_on = i16::MAX / 2;
     let v_off = 0;
     for i in 0..samples_len {
         if remain > 0 {
@@ -115,8 +123,7 @@ pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOpt
     let mut si_vec:Vec<(usize,usize)> = vec![];
     let mut status: i16 = v_off;
     let mut last: usize = 0;
-    for i in 0..samples_len {
-        let v = th_vec[i];
+    for (i, &v) in th_vec.iter().enumerate() {
         if status == v { // same as last value
             continue;
         }
@@ -137,7 +144,7 @@ pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOpt
     if opt.is_debug {
         println!("silence={:?}", si_vec);
     }
-    if si_vec.len() == 0 { return result; }
+    if si_vec.is_empty() { return result; }
 
     // reverse silence
     let mut last = 0;
@@ -154,8 +161,8 @@ pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOpt
     }
 
     // margin
-    for i in 0.. result.len() {
-        result[i].start = get_max(0, result[i].start as isize - margin_len as isize) as usize;
+    for r in result.iter_mut() {
+        r.start = get_max(0, r.start as isize - margin_len as isize) as usize;
     }
 
     // result
@@ -165,19 +172,17 @@ pub fn split_samples(samples: &mut Vec<f32>, sample_rate: u32, opt: &WavSplitOpt
     result
 }
 
-pub fn vec_sq(samples: &mut Vec<i16>) {
-    for i in 0..samples.len() {
-        let v = samples[i];
-        let v2 = v.wrapping_mul(v);
-        samples[i] = v2.abs();
+pub fn vec_sq(samples: &mut [i16]) {
+    for v in samples.iter_mut() {
+        let v2 = v.wrapping_mul(*v);
+        *v = v2.abs();
     }
 }
 
 #[allow(dead_code)]
-pub fn calc_rms2_prepare(samples: &Vec<i32>) -> Vec<i32> {
+pub fn calc_rms2_prepare(samples: &[i32]) -> Vec<i32> {
     let mut result: Vec<i32> = Vec::with_capacity(samples.len());
-    for i in 0..samples.len() {
-        let v:i32 = samples[i];
+    for &v in samples.iter() {
         let vv:i32 = v.wrapping_mul(v);
         result.push(vv);
     }
@@ -185,39 +190,56 @@ pub fn calc_rms2_prepare(samples: &Vec<i32>) -> Vec<i32> {
 }
 
 #[allow(dead_code)]
-fn calc_rms2_i(samples: &Vec<i32>, start:usize, size: usize) -> f32 {
+fn calc_rms2_i(samples: &[i32], start:usize, size: usize) -> f32 {
     let last = start + size;
     let mut total: isize = 0;
-    for i in start..last {
-        total += samples[i] as isize;
+    for v in samples[start..last].iter() {
+        total += *v as isize;
     }
     if total == 0 { return 0.0; }
     ((total as usize / size) as f32).sqrt()
 }
 
 #[allow(dead_code)]
-fn calc_rms(samples: &Vec<i16>, pos:usize, size: usize) -> i16 {
+fn calc_rms(samples: &[i16], pos:usize, size: usize) -> i16 {
     let samples_len = samples.len();
     let end = get_min((pos + size) as isize, samples_len as isize) as usize;
     let start = pos;
     let size_act = end - start + 1;
     let mut total: isize = 0;
-    for i in start..end {
-        let v = samples[i] as isize;
+    for sv in samples[start..end].iter() {
+        let v = *sv as isize;
         let v2 = v * v;
         total += v2;
     }
     ((total / size_act as isize) as f32).sqrt() as i16
 }
 
-
-pub fn sub_samples(samples: &Vec<f32>, range: WavSplitRange) -> Vec<f32> {
-    let mut result = Vec::with_capacity(range.end - range.start);
-    for i in range.start..range.end {
-        let v = samples[i];
-        result.push(v);
+/// split by the cue points (markers) of the cue chunk
+/// regions (`ltxt` with a sample length) are used as they are, otherwise the samples are cut at each marker
+pub fn split_by_cues(cue: &CueChunk, frames: usize) -> Vec<WavSplitRange> {
+    let regions: Vec<WavSplitRange> = cue.adtl.iter().filter_map(|it| match it {
+        AdtlItem::LabeledText { cue_id, sample_length, .. } if *sample_length > 0 => {
+            let point = cue.points.iter().find(|p| p.id == *cue_id)?;
+            let start = (point.sample_offset as usize).min(frames);
+            let end = (start + *sample_length as usize).min(frames);
+            if start < end { Some(WavSplitRange{start, end}) } else { None }
+        },
+        _ => None,
+    }).collect();
+    if !regions.is_empty() {
+        return regions;
     }
-    result
+    let mut marks: Vec<usize> = cue.points.iter().map(|p| (p.sample_offset as usize).min(frames)).collect();
+    marks.push(0);
+    marks.push(frames);
+    marks.sort_unstable();
+    marks.dedup();
+    marks.windows(2).map(|w| WavSplitRange{start: w[0], end: w[1]}).collect()
+}
+
+pub fn sub_samples(samples: &[f32], range: WavSplitRange) -> Vec<f32> {
+    samples[range.start..range.end].to_vec()
 }
 
 /*
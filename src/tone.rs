@@ -1,4 +1,4 @@
-/// tone generator
+//! tone generator
 
 use std::f32::consts::PI;
 
@@ -16,6 +16,12 @@ pub struct ToneOptions {
     pub bpm: f32,
     pub volume: f32, // 0.0 - 1.0
 }
+impl Default for ToneOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ToneOptions {
     pub fn new() -> Self {
         ToneOptions {
@@ -81,17 +87,17 @@ fn parse_mml(src: String) -> Vec<Note> {
             'o' => { // Octave
                 let v = it.next().expect("should be number");
                 let o = v as i32 - '0' as i32;
-                if o >= 0 && o < 9 { octave = o; }
+                if (0..9).contains(&o) { octave = o; }
             },
             'l' => { // Length
                 let v = it.next().expect("should be number");
                 let l = v as i32 - '0' as i32;
-                if l >= 1 && l <= 9 { length = l; }
+                if (1..=9).contains(&l) { length = l; }
             },
             'v' => { // Velocity
                 let n = it.next().expect("should be number");
                 let v = n as i32 - '0' as i32;
-                if v >= 0 && v <= 9 { vel = v; }
+                if (0..=9).contains(&v) { vel = v; }
             },
             _ => {}, // skip
         };
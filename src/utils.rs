@@ -1,11 +1,11 @@
-/// Wav header and samples
+//! Wav header and samples
 
 /// convert stereo to mono
 pub fn stereo_to_mono(in_v: Vec<f32>) -> Vec<f32> {
     let new_size = in_v.len() / 2;
     let mut result = Vec::with_capacity(new_size);
     for i in 0..new_size {
-        let lv = in_v[i * 2 + 0];
+        let lv = in_v[i * 2];
         let rv = in_v[i * 2 + 1];
         result.push((lv + rv) / 2.0);
     }
@@ -16,8 +16,7 @@ pub fn stereo_to_mono(in_v: Vec<f32>) -> Vec<f32> {
 pub fn mono_to_stereo(in_v: Vec<f32>) -> Vec<f32> {
     let new_size = in_v.len() * 2;
     let mut result = Vec::with_capacity(new_size);
-    for i in 0..in_v.len() {
-        let v = in_v[i];
+    for v in in_v {
         result.push(v);
         result.push(v);
     }
@@ -30,7 +29,7 @@ pub fn split_stereo_wave(samples: Vec<f32>) -> (Vec<f32>, Vec<f32>) {
     let mut l_samples = Vec::with_capacity(size);
     let mut r_samples = Vec::with_capacity(size);
     for i in 0..size {
-        l_samples.push(samples[i * 2 + 0]);
+        l_samples.push(samples[i * 2]);
         r_samples.push(samples[i * 2 + 1]);
     }
     (l_samples, r_samples)
@@ -48,6 +47,38 @@ pub fn join_stereo_wave(l_samples: Vec<f32>, r_samples: Vec<f32>) -> Vec<f32> {
     result
 }
 
+/// split interleaved samples into one Vec per channel
+pub fn deinterleave(samples: &[f32], channels: u16) -> Vec<Vec<f32>> {
+    let channels = channels.max(1) as usize;
+    let size = samples.len() / channels;
+    let mut result = vec![Vec::with_capacity(size); channels];
+    for frame in samples.chunks_exact(channels) {
+        for (ch, v) in frame.iter().enumerate() {
+            result[ch].push(*v);
+        }
+    }
+    result
+}
+
+/// join channels into interleaved samples (the shortest channel decides the length)
+pub fn interleave(channels: &[Vec<f32>]) -> Vec<f32> {
+    let size = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+    let mut result = Vec::with_capacity(size * channels.len());
+    for i in 0..size {
+        for c in channels.iter() {
+            result.push(c[i]);
+        }
+    }
+    result
+}
+
+/// convert interleaved samples with any number of channels to mono
+pub fn to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    samples.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
 
 #[cfg(test)]
 mod tests {
@@ -75,6 +106,14 @@ mod tests {
         assert_eq!(f2, f2_test);
     }
     #[test]
+    fn test_interleave_channels() {
+        let f = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0];
+        let ch = deinterleave(&f, 3);
+        assert_eq!(ch, vec![vec![1.0, 4.0, 7.0], vec![2.0, 5.0, 8.0], vec![3.0, 6.0, 9.0]]);
+        assert_eq!(interleave(&ch), f);
+        assert_eq!(to_mono(&f, 3), vec![2.0, 5.0, 8.0]);
+    }
+    #[test]
     fn test_split_stereo() {
         let f2 = vec![1.0, 1.0, 2.0, 2.0, 3.0, 3.0];
         let (f_left, f_right) = split_stereo_wave(f2);
@@ -1,7 +1,14 @@
-/// Wav file writer
+//! Wav file writer
 
-use crate::header::{WavHeader, SampleFormat, WavData};
-use std::io::{Cursor, Write, Read};
+use crate::header::{WavHeader, SampleFormat, WavData, WavExtensible, ContainerKind, Endian, SampleLayout, RawSpec, ChunkPosition};
+use crate::header::{RF64_SIZE_IN_DS64, W64_RIFF_GUID, w64_guid, chunk_id_bytes};
+use crate::g711;
+use crate::adpcm;
+use crate::aiff;
+use crate::caf;
+use crate::au;
+use crate::flac;
+use std::io::{Cursor, Write, Read, Seek, SeekFrom};
 use std::fs::File;
 
 use thiserror::Error;
@@ -20,139 +27,655 @@ pub enum EncoderError {
     IOError {
         #[source]
         source: std::io::Error
-    }
+    },
+    #[error("Invalid chunk id {id:?}, chunk ids are four bytes")]
+    InvalidChunkId {
+        id: String,
+    },
 }
 
 /// WavData to file
 pub fn to_file(file_out: &mut File, wav: &WavData) -> Result<(), EncoderError> {
     let mut w = Writer::new();
-    match w.from_scratch(&wav.header, &wav.samples) {
-        Err(err) => return Err(err),
-        Ok(_) => {},
-    }
-    match w.to_file(file_out) {
-        Err(err) => return Err(EncoderError::IOError { source: err }),
-        Ok(_) => {},
+    w.from_scratch(&wav.header, &wav.samples)?;
+    if let Err(err) = w.to_file(file_out) {
+        return Err(EncoderError::IOError { source: err });
     }
     Ok(())
 }
 
 /// WavData to bytes
-pub fn to_bytes(head: &WavHeader, samples: &Vec<f32>) -> Result<Vec<u8>, EncoderError> {
+pub fn to_bytes(head: &WavHeader, samples: &[f32]) -> Result<Vec<u8>, EncoderError> {
     let mut w = Writer::new();
-    match w.from_scratch(head, samples) {
-        Err(err) => return Err(err),
-        Ok(_) => {},
+    w.from_scratch(head, samples)?;
+    Ok(w.to_bytes())
+}
+
+/// WavData to file in the given container
+/// (use the container returned by `reader::open` to keep the original one)
+pub fn to_file_as(file_out: &mut File, wav: &WavData, container: ContainerKind) -> Result<(), EncoderError> {
+    let bytes = to_bytes_as(&wav.header, &wav.samples, container)?;
+    file_out.write_all(&bytes).map_err(|err| EncoderError::IOError { source: err })
+}
+
+/// WavData to bytes in the given container
+pub fn to_bytes_as(head: &WavHeader, samples: &[f32], container: ContainerKind) -> Result<Vec<u8>, EncoderError> {
+    if container == ContainerKind::Flac {
+        return flac::to_bytes(head, samples);
     }
+    let mut w = Writer::new();
+    w.set_container(container);
+    w.from_scratch(head, samples)?;
+    Ok(w.to_bytes())
+}
+
+/// WavData to headerless raw PCM bytes
+/// The samples are written interleaved in the format, bit depth and byte order of `spec`
+pub fn to_raw(wav: &WavData, spec: &RawSpec) -> Result<Vec<u8>, EncoderError> {
+    let mut w = Writer::new();
+    w.layout = spec.layout();
+    w.write_samples(&spec.header(), &wav.samples)?;
     Ok(w.to_bytes())
 }
 
 /// Samples: Vec<i16> to file
-pub fn i16samples_to_file(file_out: &mut File, header: &WavHeader, samples: &Vec<i16>) -> Result<(), EncoderError> {
+pub fn i16samples_to_file(file_out: &mut File, header: &WavHeader, samples: &[i16]) -> Result<(), EncoderError> {
     let mut w = Writer::new();
-    match w.from_scratch_i16(header, samples) {
-        Err(err) => return Err(err),
-        Ok(_) => {},
-    }
-    match w.to_file(file_out) {
-        Err(err) => return Err(EncoderError::IOError { source: err }),
-        Ok(_) => {},
+    w.from_scratch_i16(header, samples)?;
+    if let Err(err) = w.to_file(file_out) {
+        return Err(EncoderError::IOError { source: err });
     }
     Ok(())
 }
 
 /// Samples: Vec<i32> to file
-pub fn i32samples_to_file(file_out: &mut File, header: &WavHeader, samples: &Vec<i32>) -> Result<(), EncoderError> {
+pub fn i32samples_to_file(file_out: &mut File, header: &WavHeader, samples: &[i32]) -> Result<(), EncoderError> {
     let mut w = Writer::new();
-    match w.from_scratch_i(header, samples) {
-        Err(err) => return Err(err),
-        Ok(_) => {},
-    }
-    match w.to_file(file_out) {
-        Err(err) => return Err(EncoderError::IOError { source: err }),
-        Ok(_) => {},
+    w.from_scratch_i(header, samples)?;
+    if let Err(err) = w.to_file(file_out) {
+        return Err(EncoderError::IOError { source: err });
     }
     Ok(())
 }
 
 /// Samples: Vec<f32> to file
-pub fn f32samples_to_file(file_out: &mut File, header: &WavHeader, samples: &Vec<f32>) -> Result<(), EncoderError> {
+pub fn f32samples_to_file(file_out: &mut File, header: &WavHeader, samples: &[f32]) -> Result<(), EncoderError> {
     let mut w = Writer::new();
-    match w.from_scratch(header, samples) {
-        Err(err) => return Err(err),
-        Ok(_) => {},
-    }
-    match w.to_file(file_out) {
-        Err(err) => return Err(EncoderError::IOError { source: err }),
-        Ok(_) => {},
+    w.from_scratch(header, samples)?;
+    if let Err(err) = w.to_file(file_out) {
+        return Err(EncoderError::IOError { source: err });
     }
     Ok(())
 }
 
+/// Streaming WAV writer
+///
+/// The header is written with placeholder sizes when the writer is created,
+/// samples can then be added in any number of batches.
+/// The RIFF and data sizes are patched by `finalize` (or when the writer is dropped).
+/// A `JUNK` chunk is reserved after the RIFF header, so the file is turned into RF64
+/// if the data grows past the 32-bit limit.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    header: WavHeader,
+    /// position of the RIFF header
+    start: u64,
+    /// position of the data chunk size field
+    data_size_pos: u64,
+    /// position of the fact chunk sample length
+    fact_pos: Option<u64>,
+    /// position of the ds64 (or reserved JUNK) chunk
+    ds64_pos: Option<u64>,
+    /// RIFF, RF64, BW64, Wave64, RIFX, AIFF, AIFF-C, CAF or .au
+    container: ContainerKind,
+    /// sizes are written to the ds64 chunk
+    rf64: bool,
+    /// largest RIFF size written without promoting to RF64
+    rf64_limit: u64,
+    /// bytes of samples written
+    data_size: u64,
+    /// number of samples written
+    samples_written: u64,
+    /// ADPCM encoder and the samples waiting for a full block
+    ima: Option<adpcm::ImaEncoder>,
+    pending: Vec<f32>,
+    finalized: bool,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// write the header and return the writer
+    pub fn new(out: W, head: &WavHeader) -> Result<Self, EncoderError> {
+        Self::with_container(out, head, ContainerKind::Riff)
+    }
+    /// write the header of the container (RF64 and BW64 always use the ds64 chunk)
+    /// Wave64 has 64-bit sizes and is never promoted
+    pub fn with_container(mut out: W, head: &WavHeader, container: ContainerKind) -> Result<Self, EncoderError> {
+        let start = io_result(out.stream_position())?;
+        let mut w = Writer::new();
+        w.set_container(container);
+        w.reserve_ds64 = true;
+        w.write_riff_header(head, 0)?;
+        // check the sample format before anything is written
+        w.write_samples(head, &[])?;
+        w.write_data_header(0);
+        io_result(out.write_all(w.cur.get_ref()))?;
+        Ok(Self {
+            out,
+            header: head.clone(),
+            start,
+            data_size_pos: start + w.data_size_pos.unwrap_or(0),
+            fact_pos: w.fact_pos.map(|pos| start + pos),
+            ds64_pos: w.ds64_pos.map(|pos| start + pos),
+            // AIFF may have been changed to AIFF-C for the sample format
+            container: w.container,
+            rf64: w.rf64,
+            rf64_limit: u32::MAX as u64,
+            data_size: 0,
+            samples_written: 0,
+            ima: match head.sample_format {
+                SampleFormat::ImaAdpcm => Some(adpcm::ImaEncoder::new(head.channels, adpcm::ima_format(head).block_align)),
+                _ => None,
+            },
+            pending: Vec::new(),
+            finalized: false,
+        })
+    }
+    /// header of the file
+    pub fn header(&self) -> &WavHeader {
+        &self.header
+    }
+    /// number of sample bytes written so far
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+    /// append interleaved samples
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), EncoderError> {
+        let bytes = match &mut self.ima {
+            Some(enc) => {
+                // encode whole blocks only
+                self.pending.extend_from_slice(samples);
+                let n = self.pending.len() - self.pending.len() % enc.block_samples().max(1);
+                let bytes = enc.encode(&self.pending[..n]);
+                self.pending.drain(..n);
+                bytes
+            },
+            None => {
+                let mut w = Writer::new();
+                w.set_container(self.container);
+                w.write_samples(&self.header, samples)?;
+                w.cur.into_inner()
+            },
+        };
+        io_result(self.out.write_all(&bytes))?;
+        self.data_size += bytes.len() as u64;
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+    /// write the padding byte and the final chunk sizes
+    pub fn finalize(mut self) -> Result<(), EncoderError> {
+        self.update_sizes()
+    }
+    fn update_sizes(&mut self) -> Result<(), EncoderError> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+        // last (padded) ADPCM block
+        if let Some(enc) = &mut self.ima {
+            let bytes = enc.encode(&self.pending);
+            self.pending.clear();
+            io_result(self.out.write_all(&bytes))?;
+            self.data_size += bytes.len() as u64;
+        }
+        // if data chunk byte count is odd, one 0x00 pad byte follows (8-byte alignment in Wave64)
+        let padding = vec![0u8; self.container.chunk_padding(self.data_size) as usize];
+        io_result(self.out.write_all(&padding))?;
+        let mut w = Writer::new();
+        w.set_container(self.container);
+        w.write_unknown_chunks(&self.header, ChunkPosition::AfterData)?;
+        io_result(self.out.write_all(w.cur.get_ref()))?;
+        let end = io_result(self.out.stream_position())?;
+        let frames = self.samples_written / self.header.channels.max(1) as u64;
+        match self.container {
+            ContainerKind::Wave64 => {
+                // 64-bit sizes including the chunk headers
+                self.write_at_u64(self.start + 16, end - self.start)?;
+                self.write_at_u64(self.data_size_pos, self.data_size + 24)?;
+            },
+            ContainerKind::Caf => {
+                self.write_at_u64(self.data_size_pos, self.data_size + 4)?;
+            },
+            ContainerKind::Au => {
+                // 0xFFFFFFFF is "unknown size"
+                let data_size = self.data_size.min(u32::MAX as u64) as u32;
+                self.write_at_u32(self.data_size_pos, data_size)?;
+            },
+            ContainerKind::Aiff | ContainerKind::Aifc => {
+                // the SSND size includes the offset and block size fields
+                let form_size = (end - self.start - 8).min(u32::MAX as u64) as u32;
+                let ssnd_size = (self.data_size + 8).min(u32::MAX as u64) as u32;
+                self.write_at_u32(self.start + 4, form_size)?;
+                self.write_at_u32(self.data_size_pos, ssnd_size)?;
+            },
+            _ => self.update_riff_sizes(end - self.start - 8, frames)?,
+        }
+        if let Some(pos) = self.fact_pos {
+            self.write_at(pos, &frame_count_bytes(self.container, frames))?;
+        }
+        io_result(self.out.seek(SeekFrom::Start(end)))?;
+        io_result(self.out.flush())
+    }
+    fn update_riff_sizes(&mut self, riff_size: u64, frames: u64) -> Result<(), EncoderError> {
+        // too large for RIFF, turn the reserved JUNK chunk into ds64
+        if !self.rf64 && riff_size > self.rf64_limit {
+            if let Some(pos) = self.ds64_pos {
+                self.write_at(self.start, b"RF64")?;
+                self.write_at(pos, b"ds64")?;
+                self.rf64 = true;
+            }
+        }
+        let (riff_size32, data_size32) = match (self.rf64, self.ds64_pos) {
+            (true, Some(pos)) => {
+                let mut ds64 = Vec::new();
+                for v in [riff_size, self.data_size, frames] {
+                    ds64.extend_from_slice(&v.to_le_bytes());
+                }
+                self.write_at(pos + 8, &ds64)?;
+                (RF64_SIZE_IN_DS64, RF64_SIZE_IN_DS64)
+            },
+            _ => (riff_size.min(u32::MAX as u64) as u32, self.data_size.min(u32::MAX as u64) as u32),
+        };
+        self.write_at_u32(self.start + 4, riff_size32)?;
+        self.write_at_u32(self.data_size_pos, data_size32)
+    }
+    fn write_at(&mut self, pos: u64, bytes: &[u8]) -> Result<(), EncoderError> {
+        io_result(self.out.seek(SeekFrom::Start(pos)))?;
+        io_result(self.out.write_all(bytes))
+    }
+    /// overwrite a 32-bit header field in the byte order of the container
+    fn write_at_u32(&mut self, pos: u64, v: u32) -> Result<(), EncoderError> {
+        self.write_at(pos, &self.container.endian().u32_bytes(v))
+    }
+    /// overwrite a 64-bit header field in the byte order of the container
+    fn write_at_u64(&mut self, pos: u64, v: u64) -> Result<(), EncoderError> {
+        self.write_at(pos, &self.container.endian().u64_bytes(v))
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.update_sizes();
+    }
+}
+
+/// payloads of the typed metadata chunks of the header
+fn metadata_chunks(head: &WavHeader, endian: Endian) -> Vec<(&'static str, Vec<u8>)> {
+    let mut chunks = vec![];
+    if let Some(bext) = &head.bext {
+        chunks.push(("bext", bext.make_block(endian)));
+    }
+    if let Some(ixml) = &head.ixml {
+        chunks.push(("iXML", ixml.xml.as_bytes().to_vec()));
+    }
+    if let Some(smpl) = &head.smpl {
+        chunks.push(("smpl", smpl.make_block(endian)));
+    }
+    if let Some(inst) = &head.inst {
+        chunks.push(("inst", inst.make_block()));
+    }
+    if let Some(acid) = &head.acid {
+        chunks.push(("acid", acid.make_block(endian)));
+    }
+    if let Some(cart) = &head.cart {
+        chunks.push(("cart", cart.make_block(endian)));
+    }
+    if let Some(id3) = &head.id3 {
+        chunks.push(("id3 ", id3.make_block()));
+    }
+    if let Some(cue) = &head.cue {
+        if !cue.points.is_empty() {
+            chunks.push(("cue ", cue.make_points_block(endian)));
+        }
+        if !cue.adtl.is_empty() {
+            chunks.push(("LIST", cue.make_adtl_block(endian)));
+        }
+    }
+    chunks
+}
+
+/// sample length of the fact chunk (64 bits in Wave64, big-endian in the AIFF COMM chunk)
+fn frame_count_bytes(container: ContainerKind, frames: u64) -> Vec<u8> {
+    match container {
+        ContainerKind::Wave64 => frames.to_le_bytes().to_vec(),
+        _ => container.endian().u32_bytes(frames.min(u32::MAX as u64) as u32).to_vec(),
+    }
+}
+
+fn io_result<T>(r: std::io::Result<T>) -> Result<T, EncoderError> {
+    r.map_err(|err| EncoderError::IOError { source: err })
+}
+
 /// Generate WAV file data
 pub struct Writer {
     cur: Cursor<Vec<u8>>,
+    /// position of the sample length in the fact chunk (COMM chunk in AIFF)
+    pub(crate) fact_pos: Option<u64>,
+    /// position of the data chunk size field
+    pub(crate) data_size_pos: Option<u64>,
+    /// RIFF, RF64, BW64, Wave64, RIFX, AIFF, AIFF-C, CAF or .au
+    pub(crate) container: ContainerKind,
+    /// byte order of the header fields
+    endian: Endian,
+    /// byte order of the samples
+    layout: SampleLayout,
+    /// write a JUNK chunk that can be replaced by ds64 later
+    reserve_ds64: bool,
+    /// position of the ds64 (or JUNK) chunk
+    ds64_pos: Option<u64>,
+    /// the header uses the ds64 chunk for the sizes
+    rf64: bool,
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Writer {
     /// new struct
     pub fn new() -> Self {
         Self {
-            cur: Cursor::new(Vec::<u8>::new())
+            cur: Cursor::new(Vec::<u8>::new()),
+            fact_pos: None,
+            data_size_pos: None,
+            container: ContainerKind::Riff,
+            endian: Endian::Little,
+            layout: SampleLayout::default(),
+            reserve_ds64: false,
+            ds64_pos: None,
+            rf64: false,
         }
     }
+    /// set the container of the header
+    /// RIFF files are promoted to RF64 automatically when the data is larger than 4 GB
+    pub fn set_container(&mut self, container: ContainerKind) {
+        self.container = container;
+        // AIFF, CAF and .au are big-endian with signed 8-bit samples, RIFX big-endian with unsigned 8-bit samples
+        let aiff = matches!(container, ContainerKind::Aiff | ContainerKind::Aifc | ContainerKind::Caf | ContainerKind::Au);
+        self.endian = container.endian();
+        self.layout = SampleLayout { endian: self.endian, signed_8bit: aiff };
+    }
     /// write RIFF header
-    pub fn write_riff_header(&mut self, head: &WavHeader, data_size: u32) -> Result<(), EncoderError> {
+    pub fn write_riff_header(&mut self, head: &WavHeader, data_size: u64) -> Result<(), EncoderError> {
+        match self.container {
+            ContainerKind::Aiff | ContainerKind::Aifc => return aiff::write_header(self, head, data_size),
+            ContainerKind::Caf => return caf::write_header(self, head),
+            ContainerKind::Au => return au::write_header(self, head, data_size),
+            // FLAC frames are written by `flac::to_bytes`
+            ContainerKind::Flac => return Err(EncoderError::UnsupportedEncoding),
+            _ => {},
+        }
+        // the chunks after the data must not fail once the samples are written
+        for chunk in head.unknown_chunks.iter() {
+            self.check_chunk_id(&chunk.id)?;
+        }
         let n_bytes = (head.bits_per_sample / 8) as u32;
-        // if data chunk byte count is odd, one 0x00 pad byte follows
-        let data_pad = data_size % 2;
         // precompute LIST block to include its size in the RIFF chunk_size
-        let list_block = head.list_chunk.as_ref().map(|l| l.make_block());
-        // "LIST"(4) + size_field(4) + "INFO"(4) + block bytes
-        let list_total = list_block.as_ref().map_or(0u32, |b| 12 + b.len() as u32);
-        // chunk_size = "WAVE"(4) + fmt_chunk(24) + list_total + data_header(8) + data_size + data_pad
-        let chunk_size = 4 + 24 + list_total + (8 + data_size + data_pad);
-        // write header
-        self.write_str("RIFF");
-        self.write_u32(chunk_size);
-        self.write_str("WAVE");
-        self.write_str("fmt ");
-        self.write_u32(16);
+        let list_block = head.list_chunk.as_ref().map(|l| l.make_block_endian(self.endian));
         let audio_format = match head.sample_format {
             SampleFormat::Int => 1,
             SampleFormat::Float => 3,
+            SampleFormat::WaveFromatALaw => 6,
+            SampleFormat::WaveFormatMuLaw => 7,
+            SampleFormat::ImaAdpcm => 0x11,
             _ => return Err(EncoderError::UnsupportedEncoding),
         };
-        self.write_u16(audio_format);
+        // ADPCM is written in blocks
+        let adpcm = if audio_format == 0x11 { Some(adpcm::ima_format(head)) } else { None };
+        if let Some(f) = adpcm.as_ref().filter(|f| f.samples_per_block == 0) {
+            return Err(EncoderError::UnsupportedFormat {
+                attribute: "IMA ADPCM block align",
+                expected: &[256, 512, 1024, 2048],
+                found: f.block_align as u32,
+            });
+        }
+        let (block_align, bytes_per_sec) = match &adpcm {
+            Some(f) => {
+                let bytes_per_sec = head.sample_rate as u64 * f.block_align as u64 / f.samples_per_block.max(1) as u64;
+                (f.block_align, bytes_per_sec as u32)
+            },
+            None => (n_bytes as u16 * head.channels, head.sample_rate * n_bytes * head.channels as u32),
+        };
+        // WAVE_FORMAT_EXTENSIBLE for more than 2 channels or integer samples over 16 bits
+        let extensible = match &head.extensible {
+            _ if adpcm.is_some() => None,
+            Some(ext) => Some(ext.clone()),
+            None if head.channels > 2 || (head.sample_format == SampleFormat::Int && head.bits_per_sample > 16) => {
+                Some(WavExtensible::new(head))
+            },
+            None => None,
+        };
+        // non-PCM formats need cbSize and a fact chunk
+        let has_fact = audio_format > 3;
+        let fmt_size: u32 = if extensible.is_some() {
+            40
+        } else if adpcm.is_some() {
+            20
+        } else if has_fact {
+            18
+        } else {
+            16
+        };
+        // number of sample frames
+        let frames = match &adpcm {
+            Some(f) => data_size / f.block_align.max(1) as u64 * f.samples_per_block as u64,
+            None => data_size / block_align.max(1) as u64,
+        };
+        // chunk header + payload + padding
+        let container = self.container;
+        let chunk_total = |size: u64| container.chunk_header_size() + size + container.chunk_padding(size);
+        // dwSampleLength (64 bits in Wave64)
+        let fact_size = if container == ContainerKind::Wave64 { 8 } else { 4 };
+        let fact_total = if has_fact { chunk_total(fact_size) } else { 0 };
+        // "INFO"(4) + block bytes
+        let list_total = list_block.as_ref().map_or(0, |b| chunk_total(4 + b.len() as u64));
+        let metadata = metadata_chunks(head, self.endian);
+        let metadata_total: u64 = metadata.iter().map(|(_, b)| chunk_total(b.len() as u64)).sum();
+        let unknown_total: u64 = head.unknown_chunks.iter().map(|c| chunk_total(c.data.len() as u64)).sum();
+        let body_size = chunk_total(fmt_size as u64) + fact_total + metadata_total + list_total + unknown_total + chunk_total(data_size);
+        // write header
+        if container == ContainerKind::Wave64 {
+            // the size of the riff chunk includes its 40 bytes header
+            self.rf64 = false;
+            self.cur.write_all(&W64_RIFF_GUID).unwrap();
+            self.write_u64(40 + body_size);
+            self.cur.write_all(&w64_guid("wave")).unwrap();
+        } else {
+            // chunk_size = "WAVE"(4) + fmt_chunk + fact_chunk + list_chunk + data_chunk
+            let mut chunk_size = 4 + body_size;
+            // "ds64"(4) + size_field(4) + riffSize(8) + dataSize(8) + sampleCount(8) + tableLength(4)
+            let too_large = chunk_size + 36 > u32::MAX as u64;
+            self.rf64 = matches!(container, ContainerKind::Rf64 | ContainerKind::Bw64) || (container == ContainerKind::Riff && too_large);
+            // RIFX is never promoted
+            let reserve = self.reserve_ds64 && container == ContainerKind::Riff;
+            if self.rf64 || reserve {
+                chunk_size += 36;
+            }
+            self.write_str(match container {
+                ContainerKind::Bw64 => "BW64",
+                ContainerKind::Rifx => "RIFX",
+                _ if self.rf64 => "RF64",
+                _ => "RIFF",
+            });
+            self.write_u32(if self.rf64 { RF64_SIZE_IN_DS64 } else { chunk_size.min(u32::MAX as u64) as u32 });
+            self.write_str("WAVE");
+            if self.rf64 || reserve {
+                self.ds64_pos = Some(self.cur.position());
+                if self.rf64 {
+                    self.write_str("ds64");
+                    self.write_u32(28);
+                    self.write_u64(chunk_size);
+                    self.write_u64(data_size);
+                    self.write_u64(frames);
+                    self.write_u32(0); // no table
+                } else {
+                    self.write_str("JUNK");
+                    self.write_u32(28);
+                    self.cur.write_all(&[0; 28]).unwrap();
+                }
+            }
+        }
+        self.write_chunk_header("fmt ", fmt_size as u64);
+        self.write_u16(if extensible.is_some() { 0xFFFE } else { audio_format });
         self.write_u16(head.channels);
         self.write_u32(head.sample_rate);
-        self.write_u32(head.sample_rate * n_bytes * head.channels as u32);
-        self.write_u16(n_bytes as u16 * head.channels);
+        self.write_u32(bytes_per_sec);
+        self.write_u16(block_align);
         self.write_u16(head.bits_per_sample);
+        if let Some(ext) = extensible {
+            self.write_u16(22); // cbSize
+            self.write_u16(ext.valid_bits_per_sample);
+            self.write_u32(ext.channel_mask);
+            // keep the GUID unless it names another format than the samples are written in
+            let sub_format = match ext.sub_format_tag() {
+                Some(tag) if tag != audio_format => WavExtensible::guid_from_tag(audio_format),
+                _ => ext.sub_format,
+            };
+            self.cur.write_all(&sub_format).unwrap();
+        } else if let Some(f) = &adpcm {
+            self.write_u16(2); // cbSize
+            self.write_u16(f.samples_per_block);
+        } else if has_fact {
+            self.write_u16(0); // cbSize
+        }
+        self.write_padding(fmt_size as u64);
+        // write fact chunk (number of sample frames)
+        if has_fact {
+            self.write_chunk_header("fact", fact_size);
+            self.fact_pos = Some(self.cur.position());
+            self.cur.write_all(&frame_count_bytes(container, frames)).unwrap();
+        }
+        // write typed metadata chunks (bext, iXML, ...)
+        for (id, block) in metadata {
+            self.write_chunk_header(id, block.len() as u64);
+            self.cur.write_all(&block).unwrap();
+            self.write_padding(block.len() as u64);
+        }
         // write LIST chunk if present
         if let Some(block) = list_block {
-            self.write_str("LIST");
-            self.write_u32(block.len() as u32 + 4);
+            self.write_chunk_header("LIST", block.len() as u64 + 4);
             self.write_str("INFO");
-            self.cur.write(&block).unwrap();
+            self.cur.write_all(&block).unwrap();
+            self.write_padding(block.len() as u64 + 4);
+        }
+        self.write_unknown_chunks(head, ChunkPosition::BeforeData)
+    }
+    /// write the unknown chunks of the header at `position` (RIFF style containers only)
+    pub fn write_unknown_chunks(&mut self, head: &WavHeader, position: ChunkPosition) -> Result<(), EncoderError> {
+        if !self.container.is_riff() {
+            return Ok(());
+        }
+        for chunk in head.unknown_chunks.iter().filter(|c| c.position == position) {
+            self.check_chunk_id(&chunk.id)?;
+            self.write_chunk_header(&chunk.id, chunk.data.len() as u64);
+            self.cur.write_all(&chunk.data).unwrap();
+            self.write_padding(chunk.data.len() as u64);
         }
         Ok(())
     }
+    /// check that `id` can be written as a chunk id of the container
+    fn check_chunk_id(&self, id: &str) -> Result<(), EncoderError> {
+        let guid = self.container == ContainerKind::Wave64 && id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit());
+        if guid || chunk_id_bytes(id).is_some() {
+            return Ok(());
+        }
+        Err(EncoderError::InvalidChunkId { id: id.to_string() })
+    }
+    /// write the id and the payload size of a chunk
+    pub fn write_chunk_header(&mut self, id: &str, size: u64) {
+        if self.container == ContainerKind::Wave64 {
+            // GUID and 64-bit size including the header
+            self.cur.write_all(&w64_guid(id)).unwrap();
+            self.write_u64(size + 24);
+        } else {
+            match chunk_id_bytes(id) {
+                Some(bytes) => self.write_bytes(&bytes),
+                None => self.write_str(id),
+            }
+            self.write_u32(size.min(u32::MAX as u64) as u32);
+        }
+    }
+    /// write the padding after a chunk payload
+    pub fn write_padding(&mut self, size: u64) {
+        for _ in 0..self.container.chunk_padding(size) {
+            self.write_u8(0);
+        }
+    }
+    /// write the data chunk header (RF64 keeps the size in the ds64 chunk)
+    pub fn write_data_header(&mut self, data_size: u64) {
+        match self.container {
+            ContainerKind::Aiff | ContainerKind::Aifc => {
+                // SSND: size, offset and block size
+                self.write_str("SSND");
+                self.data_size_pos = Some(self.cur.position());
+                self.write_u32((data_size + 8).min(u32::MAX as u64) as u32);
+                self.write_u32(0);
+                self.write_u32(0);
+            },
+            ContainerKind::Wave64 => {
+                self.write_chunk_header("data", data_size);
+                self.data_size_pos = Some(self.cur.position() - 8);
+            },
+            // the samples follow the .au header
+            ContainerKind::Au => {},
+            ContainerKind::Caf => {
+                // 64-bit size including the edit count
+                self.write_str("data");
+                self.data_size_pos = Some(self.cur.position());
+                self.write_u64(data_size + 4);
+                self.write_u32(0);
+            },
+            _ => {
+                self.write_str("data");
+                self.data_size_pos = Some(self.cur.position());
+                self.write_u32(if self.rf64 { RF64_SIZE_IN_DS64 } else { data_size.min(u32::MAX as u64) as u32 });
+            },
+        }
+    }
     /// write sample to bytes
-    pub fn from_scratch(&mut self, head: &WavHeader, samples: &Vec<f32>) -> Result<(), EncoderError> {
-        let n_bytes = (head.bits_per_sample / 8) as u32;
-        let data_size = n_bytes * samples.len() as u32;
-        // if data chunk byte count is odd, one 0x00 pad byte follows
-        let data_pad = data_size % 2;
+    pub fn from_scratch(&mut self, head: &WavHeader, samples: &[f32]) -> Result<(), EncoderError> {
+        // encode samples
+        let mut body = Writer::new();
+        body.layout = self.layout;
+        body.write_samples(head, samples)?;
+        self.write_file(head, &body.cur.into_inner(), samples.len())
+    }
+    /// write the header, the encoded samples of the data chunk and the chunks after it
+    fn write_file(&mut self, head: &WavHeader, data: &[u8], sample_count: usize) -> Result<(), EncoderError> {
+        let data_size = data.len() as u64;
         // write riff header
-        match self.write_riff_header(head, data_size) {
-            Err(err) => return Err(err),
-            Ok(_) => {},
+        self.write_riff_header(head, data_size)?;
+        // exact number of sample frames
+        let frames = (sample_count / head.channels.max(1) as usize) as u64;
+        if let Some(pos) = self.fact_pos {
+            let bytes = frame_count_bytes(self.container, frames);
+            let pos = pos as usize;
+            self.cur.get_mut()[pos..pos + bytes.len()].copy_from_slice(&bytes);
+        }
+        if let (true, Some(pos)) = (self.rf64, self.ds64_pos) {
+            let pos = pos as usize + 24;
+            self.cur.get_mut()[pos..pos + 8].copy_from_slice(&frames.to_le_bytes());
         }
         // write data header
-        self.write_str("data");
-        self.write_u32(data_size);
+        self.write_data_header(data_size);
         // write samples
+        self.cur.write_all(data).unwrap();
+        self.write_padding(data_size);
+        self.write_unknown_chunks(head, ChunkPosition::AfterData)
+    }
+    /// write f32 samples (data chunk body only)
+    pub fn write_samples(&mut self, head: &WavHeader, samples: &[f32]) -> Result<(), EncoderError> {
         match head.sample_format {
             SampleFormat::Int => {
                 match head.bits_per_sample {
@@ -178,37 +701,61 @@ impl Writer {
                     }),
                 }
             },
+            SampleFormat::WaveFromatALaw | SampleFormat::WaveFormatMuLaw => {
+                if head.bits_per_sample != 8 {
+                    return Err(EncoderError::UnsupportedFormat {
+                        attribute: "bits per G.711 sample",
+                        expected: &[8],
+                        found: head.bits_per_sample as u32,
+                    });
+                }
+                for v in samples.iter() {
+                    let iv = (*v * 32768f32).clamp(-32768.0, 32767.0) as i16;
+                    if head.sample_format == SampleFormat::WaveFromatALaw {
+                        self.write_u8(g711::linear_to_alaw(iv));
+                    } else {
+                        self.write_u8(g711::linear_to_ulaw(iv));
+                    }
+                }
+            },
+            SampleFormat::ImaAdpcm => {
+                if head.bits_per_sample != 4 {
+                    return Err(EncoderError::UnsupportedFormat {
+                        attribute: "bits per IMA ADPCM sample",
+                        expected: &[4],
+                        found: head.bits_per_sample as u32,
+                    });
+                }
+                let f = adpcm::ima_format(head);
+                let mut enc = adpcm::ImaEncoder::new(head.channels, f.block_align);
+                self.cur.write_all(&enc.encode(samples)).unwrap();
+            },
             _ => return Err(EncoderError::UnsupportedEncoding),
         }
-        if data_pad == 1 { self.write_u8(0); }
         Ok(())
     }
     /// write sample(Vec<i32>) to bytes
-    pub fn from_scratch_i(&mut self, head: &WavHeader, samples: &Vec<i32>) -> Result<(), EncoderError> {
-        let n_bytes = (head.bits_per_sample / 8) as u32;
-        let data_size = n_bytes * samples.len() as u32;
-        let data_pad = data_size % 2;
-        // write riff header
-        match self.write_riff_header(head, data_size) {
-            Err(err) => return Err(err),
-            Ok(_) => {},
-        }
-        // write data header
-        self.write_str("data");
-        self.write_u32(data_size);
-        let max = std::i32::MAX as f32;
+    pub fn from_scratch_i(&mut self, head: &WavHeader, samples: &[i32]) -> Result<(), EncoderError> {
+        let mut body = Writer::new();
+        body.layout = self.layout;
+        body.write_samples_i32(head, samples)?;
+        self.write_file(head, &body.cur.into_inner(), samples.len())
+    }
+    /// write i32 samples (data chunk body only)
+    fn write_samples_i32(&mut self, head: &WavHeader, samples: &[i32]) -> Result<(), EncoderError> {
+        let max = i32::MAX as f32;
         let get_rate = |v:i32| -> f32 { v as f32 / max };
         // write samples
         match head.sample_format {
             SampleFormat::Int => {
                 match head.bits_per_sample {
-                    8 => for v in samples.iter() { self.write_u8( (get_rate(*v) * 127f32 + 127f32) as u8 ); },
-                    16 => for v in samples.iter() { self.write_i16( (get_rate(*v) * std::i16::MAX as f32) as i16); },
+                    8 => for v in samples.iter() { self.write_sample_u8( (get_rate(*v) * 127f32 + 127f32) as u8 ); },
+                    16 => for v in samples.iter() { self.write_i16( (get_rate(*v) * i16::MAX as f32) as i16); },
                     24 => {
                         let max24 = 0x7FFFFFu32 as f32;
                         for v in samples.iter() { self.write_i24( (get_rate(*v) * max24) as i32); }
                     },
-                    32 => for v in samples.iter() { self.write_i32(*v as i32); },
+                    32 => for v in samples.iter() { self.write_i32(*v); },
                     _ => return Err(EncoderError::UnsupportedFormat {
                         attribute: "bits per integer sample",
                         expected: &[8, 16, 24, 32],
@@ -229,36 +776,31 @@ impl Writer {
             },
             _ => return Err(EncoderError::UnsupportedEncoding),
         }
-        if data_pad == 1 { self.write_u8(0); }
         Ok(())
     }
 
     /// write sample(Vec<i16>) to bytes
-    pub fn from_scratch_i16(&mut self, head: &WavHeader, samples: &Vec<i16>) -> Result<(), EncoderError> {
-        let n_bytes = (head.bits_per_sample / 8) as u32;
-        let data_size = n_bytes * samples.len() as u32;
-        let data_pad = data_size % 2;
-        // write riff header
-        match self.write_riff_header(head, data_size) {
-            Err(err) => return Err(err),
-            Ok(_) => {},
-        }
-        // write data header (size excludes the pad byte)
-        self.write_str("data");
-        self.write_u32(data_size);
-        let max = std::i16::MAX as f32;
+    pub fn from_scratch_i16(&mut self, head: &WavHeader, samples: &[i16]) -> Result<(), EncoderError> {
+        let mut body = Writer::new();
+        body.layout = self.layout;
+        body.write_samples_i16(head, samples)?;
+        self.write_file(head, &body.cur.into_inner(), samples.len())
+    }
+    /// write i16 samples (data chunk body only)
+    fn write_samples_i16(&mut self, head: &WavHeader, samples: &[i16]) -> Result<(), EncoderError> {
+        let max = i16::MAX as f32;
         let get_rate = |v:i16| -> f32 { v as f32 / max };
         // write samples
         match head.sample_format {
             SampleFormat::Int => {
                 match head.bits_per_sample {
-                    8 => for v in samples.iter() { self.write_u8( (get_rate(*v) * 127f32 + 127f32) as u8 ); },
+                    8 => for v in samples.iter() { self.write_sample_u8( (get_rate(*v) * 127f32 + 127f32) as u8 ); },
                     16 => for v in samples.iter() { self.write_i16( *v ); },
                     24 => {
                         let max24 = 0x7FFFFFu32 as f32;
                         for v in samples.iter() { self.write_i24( (get_rate(*v) * max24) as i32); }
                     },
-                    32 => for v in samples.iter() { self.write_i32( (get_rate(*v) * std::i32::MAX as f32) as i32 ); },
+                    32 => for v in samples.iter() { self.write_i32( (get_rate(*v) * i32::MAX as f32) as i32 ); },
                     _ => return Err(EncoderError::UnsupportedFormat {
                         attribute: "bits per integer sample",
                         expected: &[8, 16, 24, 32],
@@ -279,7 +821,6 @@ impl Writer {
             },
             _ => return Err(EncoderError::UnsupportedEncoding),
         }
-        if data_pad == 1 { self.write_u8(0); }
         Ok(())
     }
 
@@ -288,7 +829,8 @@ impl Writer {
         let mut data:Vec<u8> = Vec::new();
         self.cur.set_position(0);
         self.cur.read_to_end(&mut data).unwrap();
-        file.write(&data)
+        file.write_all(&data)?;
+        Ok(data.len())
     }
 
     /// write bytes to Vec<u8>
@@ -301,68 +843,81 @@ impl Writer {
 
     pub fn write_str(&mut self, tag: &str) {
         let bytes:Vec<u8> = tag.bytes().collect();
-        self.cur.write(&bytes).unwrap();
+        self.cur.write_all(&bytes).unwrap();
+    }
+    /// current write position
+    pub(crate) fn position(&self) -> u64 {
+        self.cur.position()
+    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.cur.write_all(bytes).unwrap();
+    }
+    /// write little-endian bytes of a header field in the byte order of the container
+    fn write_field<const N: usize>(&mut self, mut bytes: [u8; N]) {
+        if self.endian == Endian::Big {
+            bytes.reverse();
+        }
+        self.cur.write_all(&bytes).unwrap();
+    }
+    /// write little-endian bytes of a sample in the byte order of the samples
+    fn write_sample<const N: usize>(&mut self, mut bytes: [u8; N]) {
+        if self.layout.endian == Endian::Big {
+            bytes.reverse();
+        }
+        self.cur.write_all(&bytes).unwrap();
+    }
+    /// write an unsigned 8-bit sample (signed in AIFF)
+    fn write_sample_u8(&mut self, v: u8) {
+        let v = if self.layout.signed_8bit { v.wrapping_sub(128) } else { v };
+        self.write_u8(v);
     }
     pub fn write_f32(&mut self, v: f32) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(v.to_le_bytes());
     }
     pub fn write_f64(&mut self, v: f64) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(v.to_le_bytes());
     }
     pub fn write_f32_to_u8(&mut self, v: f32) {
         let iv:u8 = ((v * 128.0) as i16 + 127) as u8;
-        let bytes = iv.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample_u8(iv);
     }
     pub fn write_f32_to_i24(&mut self, v: f32) {
         let iv = ((v * 8_388_608f32) as i32).clamp(-8_388_608, 8_388_607);
-        let bytes = iv.to_le_bytes();
-        let wb:[u8; 3] = [bytes[0], bytes[1], bytes[2]];
-        self.cur.write(&wb).unwrap();
+        self.write_sample(Self::i24_to_bytes(iv));
     }
     pub fn write_f32_to_i16(&mut self, v: f32) {
         let iv:i16 = (v * 32768f32) as i16;
-        let bytes = iv.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(iv.to_le_bytes());
     }
     pub fn write_f32_to_i32(&mut self, v: f32) {
         let iv:i32 = (v * 2_147_483_648f32) as i32;
-        let bytes = iv.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(iv.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, v: u64) {
+        self.write_field(v.to_le_bytes());
     }
     pub fn write_u32(&mut self, v: u32) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_field(v.to_le_bytes());
     }
     pub fn write_u24(&mut self, v: u32) {
-        let b0:u8 = ((v >> 0) & 0xFF) as u8;
-        let b1:u8 = ((v >> 8) & 0xFF) as u8;
-        let b2:u8 = ((v >> 16) & 0xFF) as u8;
-        let bytes = [b0, b1, b2];
-        self.cur.write(&bytes).unwrap();
+        let b = v.to_le_bytes();
+        self.write_field([b[0], b[1], b[2]]);
     }
     pub fn write_u16(&mut self, v: u16) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_field(v.to_le_bytes());
     }
     pub fn write_u8(&mut self, v: u8) {
         let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.cur.write_all(&bytes).unwrap();
     }
     pub fn write_i16(&mut self, v: i16) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(v.to_le_bytes());
     }
     pub fn write_i32(&mut self, v: i32) {
-        let bytes = v.to_le_bytes();
-        self.cur.write(&bytes).unwrap();
+        self.write_sample(v.to_le_bytes());
     }
     pub fn write_i24(&mut self, v: i32) {
-        let b = Self::i24_to_bytes(v);
-        let wb:[u8; 3] = [b[0], b[1], b[2]];
-        self.cur.write(&wb).unwrap();
+        self.write_sample(Self::i24_to_bytes(v));
     }
     pub fn i24_to_bytes(v:i32) -> [u8; 3] {
         let b = v.to_le_bytes();
@@ -397,9 +952,7 @@ mod tests {
 
     #[test]
     fn write_to_bytes() {
-        let mut samples = Vec::new();
-        samples.push(0.0);
-        samples.push(0.0);
+        let samples = vec![0.0, 0.0];
         let head = WavHeader::new_mono();
         let mut w = Writer::new();
         match w.from_scratch(&head, &samples) {
@@ -422,6 +975,138 @@ mod tests {
         }
     }
 
+    #[test]
+    fn write_extensible_header() {
+        // 16-bit stereo keeps the plain PCM header
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        let bytes = to_bytes(&head, &[0.0, 0.0]).unwrap();
+        assert_eq!(bytes.len(), 44 + 4);
+        assert_eq!(&bytes[20..22], &[1, 0]);
+
+        // 4 channels need WAVE_FORMAT_EXTENSIBLE
+        head.channels = 4;
+        let bytes = to_bytes(&head, &[0.0, 0.0, 0.0, 0.0]).unwrap();
+        assert_eq!(bytes.len(), 68 + 8);
+        assert_eq!(u32::from_le_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]), 40);
+        assert_eq!(&bytes[20..22], &[0xFE, 0xFF]);
+        assert_eq!(u16::from_le_bytes([bytes[36], bytes[37]]), 22); // cbSize
+        assert_eq!(u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]), 0x33);
+        assert_eq!(&bytes[44..60], &WavExtensible::guid_from_tag(1));
+        assert_eq!(u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize, bytes.len() - 8);
+
+        // other SubFormat GUIDs are written back (ambisonic B-format PCM)
+        let ambisonic = [0x01, 0x00, 0x00, 0x00, 0x21, 0x07, 0xD3, 0x11, 0x86, 0x44, 0xC8, 0xC1, 0xCA, 0x00, 0x00, 0x00];
+        let mut ext = WavExtensible::new(&head);
+        ext.sub_format = ambisonic;
+        head.extensible = Some(ext);
+        let bytes = to_bytes(&head, &[0.0, 0.0, 0.0, 0.0]).unwrap();
+        assert_eq!(&bytes[44..60], &ambisonic);
+        let mut r = crate::reader::Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.extensible, head.extensible);
+        assert_eq!(h.sample_format, SampleFormat::SubFormat);
+
+        // a GUID of another format tag follows the sample format
+        head.extensible.as_mut().unwrap().sub_format = WavExtensible::guid_from_tag(3);
+        let bytes = to_bytes(&head, &[0.0, 0.0, 0.0, 0.0]).unwrap();
+        assert_eq!(&bytes[44..60], &WavExtensible::guid_from_tag(1));
+    }
+
+    #[test]
+    fn write_g711_with_fact_chunk() {
+        let mut head = WavHeader::new_stereo();
+        head.sample_rate = crate::header::SAMPLE_RATE_TEL;
+        head.bits_per_sample = 8;
+        head.sample_format = SampleFormat::WaveFormatMuLaw;
+        let samples = vec![0.0, 0.5, -0.5, 0.25, 0.9, -0.9];
+        let bytes = to_bytes(&head, &samples).unwrap();
+        // RIFF(12) + fmt(8 + 18) + fact(12) + data(8) + 6
+        assert_eq!(bytes.len(), 12 + 26 + 12 + 8 + 6);
+        assert_eq!(&bytes[20..22], &[7, 0]);
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(u32::from_le_bytes([bytes[46], bytes[47], bytes[48], bytes[49]]), 3);
+
+        // streaming writer patches the fact chunk
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut cur, &head).unwrap();
+        w.write_samples(&samples[..2]).unwrap();
+        w.write_samples(&samples[2..]).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), to_bytes_with_junk(&head, &samples));
+
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::WaveFormatMuLaw);
+        let read_samples = r.get_samples_f32().unwrap();
+        assert_eq!(read_samples.len(), samples.len());
+        for (a, b) in read_samples.iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 0.02, "{a} != {b}");
+        }
+
+        // A-law
+        head.sample_format = SampleFormat::WaveFromatALaw;
+        let bytes = to_bytes(&head, &samples).unwrap();
+        assert_eq!(&bytes[20..22], &[6, 0]);
+        let wav_samples = Reader::from_vec(bytes).unwrap().get_samples_f32().unwrap();
+        for (a, b) in wav_samples.iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 0.02, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn write_ima_adpcm() {
+        let mut head = WavHeader::new_mono();
+        head.sample_rate = 8_000;
+        head.sample_format = SampleFormat::ImaAdpcm;
+        head.bits_per_sample = 4;
+        let samples: Vec<f32> = (0..1_200)
+            .map(|i| (i as f32 / 8_000.0 * 440.0 * 2.0 * std::f32::consts::PI).sin() * 0.5)
+            .collect();
+        let bytes = to_bytes(&head, &samples).unwrap();
+        // RIFF(12) + fmt(8 + 20) + fact(12) + data(8) + 3 blocks of 256 bytes
+        assert_eq!(bytes.len(), 12 + 28 + 12 + 8 + 3 * 256);
+        assert_eq!(&bytes[20..22], &[0x11, 0]);
+        assert_eq!(u16::from_le_bytes([bytes[32], bytes[33]]), 256); // nBlockAlign
+        assert_eq!(u16::from_le_bytes([bytes[38], bytes[39]]), 505); // wSamplesPerBlock
+        assert_eq!(u32::from_le_bytes([bytes[48], bytes[49], bytes[50], bytes[51]]), 1_200);
+
+        // streaming writer buffers partial blocks
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut cur, &head).unwrap();
+        for batch in samples.chunks(100) {
+            w.write_samples(batch).unwrap();
+        }
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), to_bytes_with_junk(&head, &samples));
+
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.sample_format, SampleFormat::ImaAdpcm);
+        assert_eq!(h.adpcm.unwrap().samples_per_block, 505);
+        let read_samples = r.get_samples_f32().unwrap();
+        assert_eq!(read_samples.len(), samples.len());
+        for (a, b) in read_samples.iter().zip(samples.iter()).skip(16) {
+            assert!((a - b).abs() < 0.05, "{a} != {b}");
+        }
+
+        // blocks that are not a multiple of 4 bytes keep every sample
+        head.adpcm = Some(crate::header::AdpcmFormat { block_align: 38, samples_per_block: 69, coefs: vec![] });
+        let bytes = to_bytes(&head, &samples[..1_000]).unwrap();
+        let mut r = Reader::from_vec(bytes).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(h.adpcm.unwrap().samples_per_block, 65);
+        let read_samples = r.get_samples_f32().unwrap();
+        assert_eq!(read_samples.len(), 1_000);
+        for (a, b) in read_samples.iter().zip(samples.iter()).skip(16) {
+            assert!((a - b).abs() < 0.05, "{a} != {b}");
+        }
+        // a block must hold its header
+        head.adpcm = Some(crate::header::AdpcmFormat { block_align: 2, samples_per_block: 1, coefs: vec![] });
+        assert!(to_bytes(&head, &samples).is_err());
+    }
+
     #[test]
     fn write_odd_data_size_with_padding_and_roundtrip() {
         let mut head = WavHeader::new_mono();
@@ -452,6 +1137,260 @@ mod tests {
         }
     }
 
+    #[test]
+    fn write_in_batches() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        let samples: Vec<f32> = (0..1_000).map(|i| (i as f32 / 1_000.0) - 0.5).collect();
+
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut cur, &head).unwrap();
+        for batch in samples.chunks(77) {
+            w.write_samples(batch).unwrap();
+        }
+        assert_eq!(w.data_size(), 2_000);
+        w.finalize().unwrap();
+        let bytes = cur.into_inner();
+
+        // same bytes as the in-memory writer (plus the reserved JUNK chunk)
+        assert_eq!(&bytes[12..16], b"JUNK");
+        assert!(bytes[20..48].iter().all(|b| *b == 0));
+        assert_eq!(bytes, to_bytes_with_junk(&head, &samples));
+    }
+
+    #[test]
+    fn write_sizes_on_drop() {
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 8;
+        let mut cur = Cursor::new(Vec::new());
+        {
+            let mut w = WavWriter::new(&mut cur, &head).unwrap();
+            w.write_samples(&[0.5, -0.5, 0.0]).unwrap();
+        }
+        let bytes = cur.into_inner();
+        assert_eq!(bytes.len(), 44 + 36 + 3 + 1);
+        assert_eq!(u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]), 76);
+        assert_eq!(u32::from_le_bytes([bytes[76], bytes[77], bytes[78], bytes[79]]), 3);
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.get_samples_f32().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn write_rf64() {
+        let head = WavHeader::new_stereo();
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        let mut w = Writer::new();
+        w.set_container(ContainerKind::Rf64);
+        w.from_scratch(&head, &samples).unwrap();
+        let bytes = w.to_bytes();
+        // RF64(12) + ds64(8 + 28) + fmt(8 + 16) + data(8) + 16
+        assert_eq!(bytes.len(), 12 + 36 + 24 + 8 + 16);
+        assert_eq!(&bytes[0..4], b"RF64");
+        assert_eq!(&bytes[4..8], &[0xFF; 4]);
+        assert_eq!(&bytes[12..16], b"ds64");
+        let u64_at = |pos: usize| u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+        assert_eq!(u64_at(20), bytes.len() as u64 - 8);
+        assert_eq!(u64_at(28), 16);
+        assert_eq!(u64_at(36), 2);
+        assert_eq!(&bytes[72..76], b"data");
+        assert_eq!(&bytes[76..80], &[0xFF; 4]);
+
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Rf64);
+        assert_eq!(r.data_size(), 16);
+        assert_eq!(r.get_samples_f32().unwrap(), samples);
+    }
+
+    #[test]
+    fn write_promotes_to_rf64() {
+        let head = WavHeader::new_mono();
+        let samples: Vec<f32> = (0..100).map(|i| i as f32 / 100.0).collect();
+
+        // below the limit the JUNK chunk stays
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut cur, &head).unwrap();
+        w.write_samples(&samples).unwrap();
+        w.finalize().unwrap();
+        let bytes = cur.into_inner();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[12..16], b"JUNK");
+
+        // past the limit the header is rewritten as RF64
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut cur, &head).unwrap();
+        w.rf64_limit = 256;
+        w.write_samples(&samples).unwrap();
+        w.finalize().unwrap();
+        let bytes = cur.into_inner();
+        assert_eq!(&bytes[0..4], b"RF64");
+        assert_eq!(&bytes[4..8], &[0xFF; 4]);
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(u64::from_le_bytes(bytes[28..36].try_into().unwrap()), 400);
+        assert_eq!(u64::from_le_bytes(bytes[36..44].try_into().unwrap()), 100);
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Rf64);
+        assert_eq!(r.get_samples_f32().unwrap(), samples);
+
+        // BW64 is written with the ds64 chunk from the start
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::with_container(&mut cur, &head, ContainerKind::Bw64).unwrap();
+        w.write_samples(&samples).unwrap();
+        w.finalize().unwrap();
+        let bytes = cur.into_inner();
+        assert_eq!(&bytes[0..4], b"BW64");
+        let mut r = Reader::from_vec(bytes).unwrap();
+        r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Bw64);
+        assert_eq!(r.get_samples_f32().unwrap(), samples);
+    }
+
+    #[test]
+    fn write_wave64() {
+        let mut head = WavHeader::new_mono();
+        head.sample_format = SampleFormat::WaveFormatMuLaw;
+        head.bits_per_sample = 8;
+        head.list_chunk = Some(crate::header::ListChunk {
+            items: vec![crate::header::ListChunkItem { id: "INAM".to_string(), value: "w64".to_string() }],
+        });
+        let samples = vec![0.0, 0.5, -0.5];
+        let mut w = Writer::new();
+        w.set_container(ContainerKind::Wave64);
+        w.from_scratch(&head, &samples).unwrap();
+        let bytes = w.to_bytes();
+        // riff(40) + fmt(24 + 18 + 6) + fact(24 + 8) + list(24 + 16) + data(24 + 3 + 5)
+        assert_eq!(bytes.len(), 40 + 48 + 32 + 40 + 32);
+        assert_eq!(&bytes[0..16], &W64_RIFF_GUID);
+        assert_eq!(u64::from_le_bytes(bytes[16..24].try_into().unwrap()), bytes.len() as u64);
+        assert_eq!(&bytes[24..40], &w64_guid("wave"));
+        assert_eq!(&bytes[40..56], &w64_guid("fmt "));
+        assert_eq!(u64::from_le_bytes(bytes[56..64].try_into().unwrap()), 24 + 18);
+        assert_eq!(u64::from_le_bytes(bytes[112..120].try_into().unwrap()), 3); // fact
+        assert_eq!(&bytes[160..176], &w64_guid("data"));
+        assert_eq!(u64::from_le_bytes(bytes[176..184].try_into().unwrap()), 24 + 3);
+        // only 32 hex digits are a GUID, other long ids are cut to four chars
+        let guid = w64_guid("00112233445566778899aabbccddeeff");
+        assert_eq!((guid[0], guid[15]), (0x00, 0xFF));
+        assert_eq!(crate::header::w64_chunk_id(&guid), "00112233445566778899aabbccddeeff");
+        assert_eq!(w64_guid(&"z".repeat(32)), w64_guid("zzzz"));
+        assert_eq!(w64_guid(&format!("a{}b", "€".repeat(10))), w64_guid("a???"));
+
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Wave64);
+        assert_eq!(h.sample_format, SampleFormat::WaveFormatMuLaw);
+        assert_eq!(h.list_chunk, head.list_chunk);
+        assert_eq!(r.data_size(), 3);
+        let read_samples = r.get_samples_f32().unwrap();
+        assert_eq!(read_samples.len(), 3);
+        assert!((read_samples[1] - 0.5).abs() < 0.02);
+
+        // streaming writer
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::with_container(&mut cur, &head, ContainerKind::Wave64).unwrap();
+        w.write_samples(&samples[..1]).unwrap();
+        w.write_samples(&samples[1..]).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), bytes);
+    }
+
+    #[test]
+    fn write_rifx() {
+        let mut head = WavHeader::new_stereo();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.list_chunk = Some(crate::header::ListChunk {
+            items: vec![crate::header::ListChunkItem { id: "IART".to_string(), value: "big".to_string() }],
+        });
+        let samples = vec![0.5, -0.5, 0.25, -0.25];
+        let mut w = Writer::new();
+        w.set_container(ContainerKind::Rifx);
+        w.from_scratch(&head, &samples).unwrap();
+        let bytes = w.to_bytes();
+        // RIFX(12) + fmt(8 + 16) + LIST(12 + 12) + data(8) + 8
+        assert_eq!(bytes.len(), 12 + 24 + 24 + 8 + 8);
+        assert_eq!(&bytes[0..4], b"RIFX");
+        assert_eq!(u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        assert_eq!(u32::from_be_bytes(bytes[16..20].try_into().unwrap()), 16);
+        assert_eq!(u16::from_be_bytes([bytes[22], bytes[23]]), 2); // channels
+        assert_eq!(&bytes[bytes.len() - 8..bytes.len() - 6], &[0x40, 0x00]);
+
+        let mut r = Reader::from_vec(bytes.clone()).unwrap();
+        let h = r.read_header().unwrap();
+        assert_eq!(r.container(), ContainerKind::Rifx);
+        assert_eq!(h.channels, 2);
+        assert_eq!(h.sample_rate, 44_100);
+        assert_eq!(h.list_chunk, head.list_chunk);
+        for (a, b) in r.get_samples_f32().unwrap().iter().zip(samples.iter()) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+
+        // streaming writer
+        let mut cur = Cursor::new(Vec::new());
+        let mut w = WavWriter::with_container(&mut cur, &head, ContainerKind::Rifx).unwrap();
+        w.write_samples(&samples).unwrap();
+        w.finalize().unwrap();
+        assert_eq!(cur.into_inner(), bytes);
+    }
+
+    #[test]
+    fn write_integer_samples_like_f32_samples() {
+        // the chunks after the data are written and counted in the RIFF size
+        let mut head = WavHeader::new_mono();
+        head.set_int_format();
+        head.bits_per_sample = 16;
+        head.unknown_chunks = vec![crate::header::UnknownChunk {
+            id: "tail".to_string(), data: vec![1, 2, 3, 4], position: ChunkPosition::AfterData,
+        }];
+        let path = std::env::temp_dir().join("wav_io_i16_after_data.wav");
+        i16samples_to_file(&mut File::create(&path).unwrap(), &head, &[1000, -1000, 500]).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        // RIFF(12) + fmt(24) + data(8 + 6) + tail(8 + 4)
+        assert_eq!(bytes.len(), 12 + 24 + 14 + 12);
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize, bytes.len() - 8);
+        let (wav, _) = crate::reader::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(wav.header.unknown_chunks, head.unknown_chunks);
+        assert_eq!(wav.samples.len(), 3);
+
+        // the ds64 chunk has the number of frames
+        head.channels = 2;
+        for i32_samples in [false, true] {
+            let mut w = Writer::new();
+            w.set_container(ContainerKind::Rf64);
+            if i32_samples {
+                w.from_scratch_i(&head, &[1 << 20; 6]).unwrap();
+            } else {
+                w.from_scratch_i16(&head, &[1000; 6]).unwrap();
+            }
+            let bytes = w.to_bytes();
+            let ds64 = bytes.windows(4).position(|id| id == b"ds64").unwrap();
+            assert_eq!(u64::from_le_bytes(bytes[ds64 + 24..ds64 + 32].try_into().unwrap()), 3);
+            assert_eq!(&bytes[bytes.len() - 12..bytes.len() - 8], b"tail");
+        }
+    }
+
+    #[test]
+    fn write_unsupported_format_fails_early() {
+        let mut head = WavHeader::new_mono();
+        head.bits_per_sample = 12;
+        let mut cur = Cursor::new(Vec::new());
+        assert!(WavWriter::new(&mut cur, &head).is_err());
+        assert!(cur.into_inner().is_empty());
+    }
+
+    /// in-memory writer output with the JUNK chunk reserved by WavWriter
+    fn to_bytes_with_junk(head: &WavHeader, samples: &[f32]) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.reserve_ds64 = true;
+        w.from_scratch(head, samples).unwrap();
+        w.to_bytes()
+    }
+
     fn decode_i24_le(bytes: [u8; 3]) -> i32 {
         let raw = (bytes[0] as i32) | ((bytes[1] as i32) << 8) | ((bytes[2] as i32) << 16);
         if (raw & 0x0080_0000) != 0 {